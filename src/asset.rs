@@ -16,12 +16,18 @@ impl Assets {
         let sprite = &self.sprite;
         let region = Size::square(16).to_region();
         let mini_region = Size::square(8).to_region().move_y(48);
+        let digit_region = mini_region.shift_y(1);
         Ok(CellSprites {
             just: sprite.clip(region).or_fail()?,
             over: sprite.clip(region.shift_x(1)).or_fail()?,
             mine: sprite.clip(region.shift_x(2)).or_fail()?,
             mine_alpha: sprite.clip(region.shift_x(4)).or_fail()?,
             focus: sprite.clip(region.shift_x(3)).or_fail()?,
+            safe: sprite.clip(region.shift_x(10)).or_fail()?,
+            anti_mine: sprite.clip(region.shift_x(11)).or_fail()?,
+            double_mine: sprite.clip(region.shift_y(1).shift_x(10)).or_fail()?,
+            clueless: sprite.clip(region.shift_y(1).shift_x(11)).or_fail()?,
+            hidden: sprite.clip(region.shift_y(1).shift_x(12)).or_fail()?,
             warning: sprite.clip(region.shift_y(1).shift_x(0)).or_fail()?,
             numbers: [
                 sprite.clip(region.shift_y(1).shift_x(1)).or_fail()?,
@@ -45,6 +51,19 @@ impl Assets {
                 sprite.clip(mini_region.shift_x(7)).or_fail()?,
                 sprite.clip(mini_region.shift_x(8)).or_fail()?,
             ],
+            digits: [
+                sprite.clip(digit_region.shift_x(0)).or_fail()?,
+                sprite.clip(digit_region.shift_x(1)).or_fail()?,
+                sprite.clip(digit_region.shift_x(2)).or_fail()?,
+                sprite.clip(digit_region.shift_x(3)).or_fail()?,
+                sprite.clip(digit_region.shift_x(4)).or_fail()?,
+                sprite.clip(digit_region.shift_x(5)).or_fail()?,
+                sprite.clip(digit_region.shift_x(6)).or_fail()?,
+                sprite.clip(digit_region.shift_x(7)).or_fail()?,
+                sprite.clip(digit_region.shift_x(8)).or_fail()?,
+                sprite.clip(digit_region.shift_x(9)).or_fail()?,
+            ],
+            minus: sprite.clip(digit_region.shift_x(10)).or_fail()?,
         })
     }
 
@@ -105,6 +124,42 @@ impl Assets {
         let region = Size::from_wh(24, 24).to_region().move_y(32).move_x(192);
         sprite.clip(region).or_fail()
     }
+
+    pub fn daily_button_sprite(&self) -> Result<Sprite> {
+        let sprite = &self.sprite;
+        let region = Size::from_wh(24, 24).to_region().move_y(136);
+        sprite.clip(region).or_fail()
+    }
+
+    pub fn win_panel_sprite(&self) -> Result<Sprite> {
+        let sprite = &self.sprite;
+        let region = Size::from_wh(130, 94).to_region().move_y(160);
+        sprite.clip(region).or_fail()
+    }
+
+    pub fn session_panel_sprite(&self) -> Result<Sprite> {
+        let sprite = &self.sprite;
+        let region = Size::from_wh(72, 52).to_region().move_y(160).move_x(136);
+        sprite.clip(region).or_fail()
+    }
+
+    pub fn replay_button_sprite(&self) -> Result<Sprite> {
+        let sprite = &self.sprite;
+        let region = Size::from_wh(24, 24).to_region().move_y(136).move_x(24);
+        sprite.clip(region).or_fail()
+    }
+
+    pub fn new_board_button_sprite(&self) -> Result<Sprite> {
+        let sprite = &self.sprite;
+        let region = Size::from_wh(24, 24).to_region().move_y(136).move_x(48);
+        sprite.clip(region).or_fail()
+    }
+
+    pub fn hint_button_sprite(&self) -> Result<Sprite> {
+        let sprite = &self.sprite;
+        let region = Size::from_wh(24, 24).to_region().move_y(32).move_x(216);
+        sprite.clip(region).or_fail()
+    }
 }
 
 fn decode_sprite(png: &[u8]) -> Result<Sprite> {
@@ -146,6 +201,21 @@ pub struct CellSprites {
     pub mine: Sprite,
     pub mine_alpha: Sprite,
     pub focus: Sprite,
+    pub safe: Sprite,
+    pub anti_mine: Sprite,
+    pub double_mine: Sprite,
+
+    /// Wormhole cell that is drawn but shows no clue.
+    pub clueless: Sprite,
+
+    /// Cell whose clue is not revealed yet in the fog-of-war mode.
+    pub hidden: Sprite,
     pub numbers: [Sprite; 9],
     pub mini_numbers: [Sprite; 8],
+
+    /// Plain digits to draw numbers that don't fit `numbers` or `mini_numbers`.
+    pub digits: [Sprite; 10],
+
+    /// Sign of negative clues, which appear on boards with anti-mines.
+    pub minus: Sprite,
 }
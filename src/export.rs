@@ -0,0 +1,158 @@
+use crate::model::{Model, State};
+use crate::replay::ReplayEvent;
+use crate::view::Window;
+use orfail::OrFail;
+use pagurus::image::Canvas;
+use pagurus::spatial::{Position, Size};
+use pagurus::video::{PixelFormat, VideoFrame, VideoFrameSpec};
+use pagurus::{Result, System};
+use std::time::Duration;
+
+pub const MAX_SCALE: u32 = 8;
+pub const MAX_FPS: u32 = 60;
+
+// How long the final board is shown before the animation loops.
+const LAST_FRAME_DURATION: Duration = Duration::from_secs(2);
+
+/// Renders the window into an off-screen RGB image that is magnified `scale` times.
+pub fn render_image(window: &Window, model: &Model, scale: u32) -> Result<Image> {
+    (1..=MAX_SCALE)
+        .contains(&scale)
+        .or_fail_with(|_| format!("Scale should be between 1 and {MAX_SCALE}: {scale}"))?;
+
+    let resolution = window.window_size(model);
+    let mut frame = VideoFrame::new(VideoFrameSpec {
+        pixel_format: PixelFormat::Rgb24,
+        resolution,
+        stride: resolution.width,
+    });
+    window
+        .render(&mut Canvas::new(&mut frame), model)
+        .or_fail()?;
+
+    let size = Size::from_wh(resolution.width * scale, resolution.height * scale);
+    let mut data = Vec::with_capacity((size.width * size.height) as usize * 3);
+    for y in 0..size.height {
+        for x in 0..size.width {
+            let (r, g, b) =
+                frame.read_rgb(Position::from_xy((x / scale) as i32, (y / scale) as i32));
+            data.extend_from_slice(&[r, g, b]);
+        }
+    }
+    Ok(Image { size, data })
+}
+
+/// Renders the window and encodes it as a PNG image.
+pub fn screenshot(window: &Window, model: &Model, scale: u32) -> Result<Vec<u8>> {
+    let image = render_image(window, model, scale).or_fail()?;
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, image.size.width, image.size.height);
+    encoder.set_color(png::ColorType::Rgb);
+    let mut writer = encoder.write_header().or_fail()?;
+    writer.write_image_data(&image.data).or_fail()?;
+    writer.finish().or_fail()?;
+    Ok(png)
+}
+
+/// Plays `events` back on `model` and encodes the rendered frames as an animated PNG (APNG).
+///
+/// `model` should be at the beginning of the replayed game (see [`Model::start_replay()`]).
+/// Consecutive identical frames are merged into one, so the size of the output mainly depends on
+/// the number of events rather than the length of the game.
+///
+/// As the number of frames has to be known before the first one is written, the game is played
+/// back twice: once to find the frames and their durations, and once more to encode them.
+/// This way, only a frame or two are kept in memory at a time.
+pub fn replay_animation<S: System>(
+    system: &S,
+    window: &Window,
+    model: &mut Model,
+    events: &[ReplayEvent],
+    fps: u32,
+    scale: u32,
+) -> Result<Vec<u8>> {
+    (1..=MAX_FPS)
+        .contains(&fps)
+        .or_fail_with(|_| format!("FPS should be between 1 and {MAX_FPS}: {fps}"))?;
+
+    // Each frame is shown for `ticks / fps` seconds.
+    let mut frame_ticks: Vec<u32> = Vec::new();
+    let mut last_image: Option<Image> = None;
+    play_back(system, &mut model.clone(), events, fps, |_, model| {
+        let image = render_image(window, model, scale).or_fail()?;
+        match frame_ticks.last_mut() {
+            Some(ticks) if last_image.as_ref() == Some(&image) => *ticks += 1,
+            _ => {
+                frame_ticks.push(1);
+                last_image = Some(image);
+            }
+        }
+        Ok(())
+    })
+    .or_fail()?;
+    *frame_ticks.last_mut().or_fail()? += (LAST_FRAME_DURATION * fps).as_secs() as u32;
+    let size = last_image.or_fail()?.size;
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, size.width, size.height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder
+        .set_animated(frame_ticks.len() as u32, 0)
+        .or_fail()?;
+    let mut writer = encoder.write_header().or_fail()?;
+    let mut frame_ticks = frame_ticks.into_iter();
+    let mut next_frame_tick = 0;
+    play_back(system, model, events, fps, |tick, model| {
+        if tick != next_frame_tick {
+            return Ok(());
+        }
+        let ticks = frame_ticks.next().or_fail()?;
+        next_frame_tick += ticks;
+        let ticks = u16::try_from(ticks)
+            .or_fail_with(|_| format!("Too long frame: {ticks} ticks at {fps} FPS"))?;
+        let image = render_image(window, model, scale).or_fail()?;
+        writer.set_frame_delay(ticks, fps as u16).or_fail()?;
+        writer.write_image_data(&image.data).or_fail()?;
+        Ok(())
+    })
+    .or_fail()?;
+    writer.finish().or_fail()?;
+    Ok(png)
+}
+
+// Plays `events` back on `model` at `fps`, calling `f` with the tick number at each tick.
+fn play_back<S, F>(
+    system: &S,
+    model: &mut Model,
+    events: &[ReplayEvent],
+    fps: u32,
+    mut f: F,
+) -> Result<()>
+where
+    S: System,
+    F: FnMut(u32, &Model) -> Result<()>,
+{
+    let mut next = 0;
+    for tick in 0.. {
+        let time = Duration::from_secs(u64::from(tick)) / fps;
+        while let Some(&event) = events.get(next).filter(|event| event.time <= time) {
+            model.apply_replay_event(system, event);
+            next += 1;
+        }
+        if model.state() == State::Playing {
+            model.set_elapsed_time(system, time);
+        }
+        f(tick, model).or_fail()?;
+        if next == events.len() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// RGB24 image without padding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub size: Size,
+    pub data: Vec<u8>,
+}
@@ -1,9 +1,14 @@
-use crate::model::Level;
+use crate::export;
+use crate::model::{Level, State};
+use crate::replay::{Replay, ReplayEvent};
+use crate::session::{Session, SessionMode};
+use crate::stats::Stats;
 use crate::tag;
 use crate::{model::Model, view::Window};
 use orfail::{Failure, OrFail};
+use pagurus::event::{Event, MouseEvent};
 use pagurus::image::{Canvas, Color};
-use pagurus::{event::Event, fixed_window::FixedWindow, video::VideoFrame, Result, System};
+use pagurus::{fixed_window::FixedWindow, video::VideoFrame, Result, System};
 use std::collections::VecDeque;
 use std::time::Duration;
 
@@ -12,6 +17,10 @@ pagurus::export_wasm_functions!(Game);
 
 const FPS: u64 = 30;
 const RENDER_TIMEOUT_DURATION: Duration = Duration::from_micros(1_000_000 / FPS);
+const LONG_PRESS_TIMEOUT_DURATION: Duration = Duration::from_millis(500);
+
+// How long a solved board of a session is shown before the next one starts.
+const NEXT_BOARD_TIMEOUT_DURATION: Duration = Duration::from_secs(1);
 
 #[derive(Debug, Default)]
 pub struct Game {
@@ -19,12 +28,40 @@ pub struct Game {
     fixed_window: FixedWindow,
     window: Window,
     model: Model,
+    stats: Stats,
+    playback: Option<Playback>,
+    session: Option<Session>,
+
+    // Whether the query string started a game or a session, which takes precedence over a saved game.
+    started_by_query: bool,
+
     action_queue: VecDeque<Action>,
 }
 
 impl Game {
     fn render<S: System>(&mut self, system: &mut S) -> Result<()> {
-        self.model.update_elapsed_time(system);
+        if self.playback.is_some() {
+            self.advance_playback(system);
+        } else {
+            self.model.update_elapsed_time(system);
+        }
+        self.model.update_today(system);
+
+        let session = self.session.as_ref();
+        let board_time = match self.model.state() {
+            State::Playing => self.model.elapsed_time(),
+            _ => Duration::ZERO,
+        };
+        self.window.set_session_clock(
+            session
+                .filter(|session| !session.is_finished())
+                .map(|session| session.clock(system.clock_game_time(), board_time)),
+        );
+        self.window.set_session_summary(
+            session
+                .filter(|session| session.is_finished())
+                .map(Session::summary),
+        );
 
         let mut canvas = Canvas::new(&mut self.video_frame);
         canvas.fill_color(Color::BLACK);
@@ -37,6 +74,114 @@ impl Game {
         system.video_draw(self.video_frame.as_ref());
         Ok(())
     }
+
+    // Applies the recorded events whose time has come.
+    fn advance_playback<S: System>(&mut self, system: &S) {
+        let Some(playback) = &mut self.playback else {
+            return;
+        };
+        let elapsed_time = (system.clock_game_time() - playback.start_time) * playback.speed;
+        while let Some(event) = playback.events.get(playback.next) {
+            if event.time > elapsed_time {
+                break;
+            }
+            self.model.apply_replay_event(system, *event);
+            playback.next += 1;
+        }
+        let finished = playback.next == playback.events.len();
+        if self.model.state() == State::Playing {
+            self.model.set_elapsed_time(system, elapsed_time);
+        }
+        if finished {
+            self.end_playback();
+        }
+    }
+
+    // Ends the playback, after which the player can go on with the replayed game if it is unfinished.
+    // Such a game is counted as started, so that winning it is recorded consistently.
+    fn end_playback(&mut self) {
+        if self.playback.take().is_some() && self.model.state() == State::Playing {
+            self.stats.record_start(self.model.level());
+        }
+    }
+
+    // Starts a game outside of any session (the current session, if any, is abandoned).
+    //
+    // If no board can be generated (see `Model::start_game()`), the error is logged and
+    // the current game goes on as if nothing happened. Returns `true` if a game has started.
+    fn start_game<S: System>(&mut self, system: &mut S, level: Level) -> bool {
+        let window_size = self.window.window_size(&self.model);
+        if let Err(e) = self.model.start_game(system, level) {
+            pagurus::println!("Failed to start a game: {e}");
+            return false;
+        }
+        self.playback = None;
+        self.session = None;
+        self.stats.record_start(self.model.level());
+
+        // The previous board may have been of another size (e.g., a replay of another level).
+        if self.window.window_size(&self.model) != window_size {
+            self.resize_window(system);
+        }
+        true
+    }
+
+    fn start_session<S: System>(&mut self, system: &mut S, mode: SessionMode) -> Result<()> {
+        let session = Session::new(mode, system.clock_game_time());
+        if !self.start_game(system, session.level().or_fail()?) {
+            return Ok(());
+        }
+        if let SessionMode::TimeAttack { time_limit } = mode {
+            system.clock_set_timeout(tag::SESSION_TIMEOUT, time_limit);
+        }
+        self.session = Some(session);
+        Ok(())
+    }
+
+    // Starts the next board of the session once the current one has been won.
+    fn start_next_board<S: System>(&mut self, system: &mut S) -> Result<()> {
+        let Some(level) = self
+            .session
+            .as_ref()
+            .filter(|session| !session.is_finished())
+            .and_then(Session::level)
+        else {
+            return Ok(());
+        };
+        if !matches!(self.model.state(), State::Won { .. }) {
+            return Ok(());
+        }
+        let session = self.session.take();
+        self.start_game(system, level);
+        self.session = session;
+        Ok(())
+    }
+
+    // Records the win if the game has just been won (not in a replay),
+    // and moves on to the next board of the session, if any.
+    fn record_win<S: System>(&mut self, system: &mut S, was_playing: bool) {
+        let State::Won { elapsed_time } = self.model.state() else {
+            return;
+        };
+        if !was_playing || self.playback.is_some() {
+            return;
+        }
+        self.stats.record_win(self.model.level(), elapsed_time);
+        if let Some(date) = self.model.daily_date() {
+            self.stats.record_daily_win(date, elapsed_time);
+        }
+        if let Some(session) = self.session.as_mut().filter(|s| !s.is_finished()) {
+            session.record_win(elapsed_time);
+            if !session.is_finished() {
+                system.clock_set_timeout(tag::NEXT_BOARD_TIMEOUT, NEXT_BOARD_TIMEOUT_DURATION);
+            }
+        }
+    }
+
+    fn resize_window<S: System>(&mut self, system: &mut S) {
+        self.fixed_window = FixedWindow::new(self.window.window_size(&self.model));
+        self.video_frame = VideoFrame::new(system.video_init(self.fixed_window.size()));
+    }
 }
 
 impl<S: System> pagurus::Game<S> for Game {
@@ -51,6 +196,11 @@ impl<S: System> pagurus::Game<S> for Game {
 
     fn handle_event(&mut self, system: &mut S, event: Event) -> Result<bool> {
         let event = self.fixed_window.handle_event(event);
+        if matches!(event, Event::Key(_) | Event::Mouse(MouseEvent::Down { .. })) {
+            // The player can take over the replayed game at any time.
+            self.end_playback();
+        }
+        let was_playing = self.model.state() == State::Playing && self.playback.is_none();
         match event {
             Event::WindowResized(_) => {
                 self.video_frame = VideoFrame::new(system.video_init(self.fixed_window.size()));
@@ -61,26 +211,68 @@ impl<S: System> pagurus::Game<S> for Game {
                 system.clock_set_timeout(tag::RENDERING_TIMEOUT, RENDER_TIMEOUT_DURATION);
             }
             Event::Timeout(tag::START_8X15_TIMEOUT) => {
-                self.model.start_game(system, Level::Small).or_fail()?;
+                self.start_game(system, Level::Small);
                 self.render(system).or_fail()?;
             }
             Event::Timeout(tag::START_16X30_TIMEOUT) => {
-                self.model.start_game(system, Level::Large).or_fail()?;
+                self.start_game(system, Level::Large);
                 self.render(system).or_fail()?;
             }
             Event::Timeout(tag::START_16X30_WITH_WORMHOLE_TIMEOUT) => {
-                self.model
-                    .start_game(system, Level::LargeWithWormhole)
-                    .or_fail()?;
+                self.start_game(system, Level::LargeWithWormhole);
+                self.render(system).or_fail()?;
+            }
+            Event::Timeout(tag::START_DAILY_TIMEOUT) => {
+                self.start_game(system, Level::Daily);
+                self.render(system).or_fail()?;
+            }
+            Event::Timeout(tag::LONG_PRESS_TIMEOUT) => {
+                self.window.handle_long_press_timeout(&mut self.model);
+            }
+            Event::Timeout(tag::SESSION_TIMEOUT) => {
+                // The timeout may be left over from an abandoned session.
+                let now = system.clock_game_time();
+                if let Some(session) = self.session.as_mut().filter(|s| s.is_time_up(now)) {
+                    session.finish();
+                    self.render(system).or_fail()?;
+                }
+            }
+            Event::Timeout(tag::NEXT_BOARD_TIMEOUT) => {
+                self.start_next_board(system).or_fail()?;
                 self.render(system).or_fail()?;
             }
+            _ if self.session.as_ref().is_some_and(Session::is_finished) => {
+                // The session summary is closed by any key or button press.
+                if matches!(event, Event::Key(_) | Event::Mouse(MouseEvent::Down { .. })) {
+                    self.session = None;
+                }
+            }
             _ => {
+                if self.playback.is_none() {
+                    self.model.update_elapsed_time(system);
+                }
                 self.window.handle_event(event, &mut self.model).or_fail()?;
             }
         }
+        self.record_win(system, was_playing);
+        if self.window.take_long_press_requested() {
+            system.clock_set_timeout(tag::LONG_PRESS_TIMEOUT, LONG_PRESS_TIMEOUT_DURATION);
+        }
         if self.window.take_help_button_clicked() {
             self.action_queue.push_back(Action::OpenHelp);
         }
+        if self.window.take_hint_button_clicked() {
+            self.model.show_hint();
+        }
+        if self.window.take_replay_button_clicked() {
+            if let Some(seed) = self.model.seed() {
+                self.model.set_next_seed(seed);
+            }
+            self.start_game(system, self.model.level());
+        }
+        if self.window.take_new_board_button_clicked() {
+            self.start_game(system, self.model.level());
+        }
         if self.window.take_start_8x15_button_clicked() {
             system.clock_set_timeout(tag::START_8X15_TIMEOUT, Duration::from_secs(0));
         }
@@ -93,6 +285,9 @@ impl<S: System> pagurus::Game<S> for Game {
                 Duration::from_secs(0),
             );
         }
+        if self.window.take_start_daily_button_clicked() {
+            system.clock_set_timeout(tag::START_DAILY_TIMEOUT, Duration::from_secs(0));
+        }
         if self.window.take_start_custom_button_clicked() {
             // Reuse `START_16X30_WITH_WORMHOLE_TIMEOUT` tag for custom level
             system.clock_set_timeout(
@@ -104,13 +299,49 @@ impl<S: System> pagurus::Game<S> for Game {
         Ok(true)
     }
 
-    fn query(&mut self, _system: &mut S, name: &str) -> Result<Vec<u8>> {
+    fn query(&mut self, system: &mut S, name: &str) -> Result<Vec<u8>> {
+        // Some queries take colon-separated arguments (e.g., "screenshot:2").
+        let mut args = name.split(':');
+        let name = args.next().unwrap_or_default();
         match name {
             "nextAction" => {
                 if let Some(action) = self.action_queue.pop_front() {
                     return serde_json::to_vec(&action).or_fail();
                 }
             }
+            "seed" => return serde_json::to_vec(&self.model.seed()).or_fail(),
+            "difficulty" => return serde_json::to_vec(&self.model.difficulty()).or_fail(),
+            "hintsUsed" => return serde_json::to_vec(&self.model.hints_used()).or_fail(),
+            "dailyRecord" => return serde_json::to_vec(&self.stats.daily_record()).or_fail(),
+            "saveState" => return serde_json::to_vec(&self.model).or_fail(),
+            "replay" => return serde_json::to_vec(self.model.replay()).or_fail(),
+            "saveStats" => return serde_json::to_vec(&self.stats).or_fail(),
+            "sessionSummary" => {
+                let summary = self.session.as_ref().map(Session::summary);
+                return serde_json::to_vec(&summary).or_fail();
+            }
+            "screenshot" => {
+                let scale = parse_query_arg(args.next(), 1).or_fail()?;
+                return export::screenshot(&self.window, &self.model, scale).or_fail();
+            }
+            "replayAnimation" => {
+                let fps = parse_query_arg(args.next(), 10).or_fail()?;
+                let scale = parse_query_arg(args.next(), 1).or_fail()?;
+
+                // The current game is replayed on a copy of the model, so it is not affected.
+                let replay = self.model.replay().clone();
+                let mut model = self.model.clone();
+                model.start_replay(system, &replay).or_fail()?;
+                return export::replay_animation(
+                    system,
+                    &self.window,
+                    &mut model,
+                    &replay.events,
+                    fps,
+                    scale,
+                )
+                .or_fail();
+            }
             _ => return Err(Failure::new(format!("unknown query: {name:?}"))),
         }
         Ok(vec![])
@@ -120,11 +351,67 @@ impl<S: System> pagurus::Game<S> for Game {
         match name {
             "setQueryString" => {
                 let qs = std::str::from_utf8(data).or_fail()?;
-                if let Some(level) = Level::from_qs(qs).or_fail()? {
+                let params = Level::from_qs(qs).or_fail()?;
+                if let Some(seed) = params.seed {
+                    self.model.set_next_seed(seed);
+                }
+                self.model.set_unique(params.unique);
+                self.window.set_ghost_border(params.ghost_border);
+                if let Some(level) = params.level {
                     self.model.set_custom_level(level);
-                    self.fixed_window = FixedWindow::new(self.window.window_size(&self.model));
-                    self.video_frame = VideoFrame::new(system.video_init(self.fixed_window.size()));
+                    self.resize_window(system);
                 }
+                self.started_by_query = if let Some(mode) = params.session {
+                    self.start_session(system, mode).or_fail()?;
+                    self.session.is_some()
+                } else if let Some(level) = params.start {
+                    self.start_game(system, level)
+                } else {
+                    false
+                };
+                Ok(())
+            }
+            "loadState" => {
+                if self.started_by_query {
+                    return Ok(());
+                }
+                let saved = serde_json::from_slice(data).or_fail()?;
+                self.model.restore(system, saved).or_fail()?;
+                self.playback = None;
+                self.session = None;
+                self.resize_window(system);
+                Ok(())
+            }
+            "playReplay" => {
+                let params: PlaybackParams = serde_json::from_slice(data).or_fail()?;
+                matches!(params.speed, 1 | 2 | 4)
+                    .or_fail_with(|_| "Playback speed should be 1, 2 or 4".to_owned())?;
+                let replay = params.replay.unwrap_or_else(|| self.model.replay().clone());
+                self.model.start_replay(system, &replay).or_fail()?;
+                self.resize_window(system);
+                self.session = None;
+                self.playback = Some(Playback {
+                    events: replay.events,
+                    next: 0,
+                    speed: params.speed,
+                    start_time: system.clock_game_time(),
+                });
+                Ok(())
+            }
+            "loadStats" => {
+                self.stats = serde_json::from_slice(data).or_fail()?;
+                Ok(())
+            }
+            "undo" => {
+                self.model.update_elapsed_time(system);
+                self.model.undo();
+                Ok(())
+            }
+            "redo" => {
+                let was_playing = self.model.state() == State::Playing;
+                self.model.update_elapsed_time(system);
+                self.model.redo();
+                self.record_win(system, was_playing);
                 Ok(())
             }
             _ => Err(Failure::new(format!("unknown command: {name:?}"))),
@@ -132,7 +419,774 @@ impl<S: System> pagurus::Game<S> for Game {
     }
 }
 
+fn parse_query_arg(arg: Option<&str>, default: u32) -> Result<u32> {
+    arg.map_or(Ok(default), |arg| {
+        arg.parse::<u32>()
+            .or_fail_with(|e| format!("Invalid query argument {arg:?}: {e}"))
+    })
+}
+
+/// Parameters of the `playReplay` command.
+///
+/// If `replay` is omitted, the current (usually finished) game is played back.
+#[derive(Debug, serde::Deserialize)]
+struct PlaybackParams {
+    speed: u32,
+    #[serde(default)]
+    replay: Option<Replay>,
+}
+
+#[derive(Debug)]
+struct Playback {
+    events: Vec<ReplayEvent>,
+    next: usize,
+    speed: u32,
+    start_time: Duration,
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub enum Action {
     OpenHelp,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{assert_golden_frame, MockSystem};
+    use crate::session::{SessionSummary, MARATHON_LEVELS};
+    use crate::solver::Difficulty;
+    use pagurus::{event::MouseEvent, spatial::Position, Game as _};
+
+    const START_8X15_BUTTON: Position = Position::from_xy(134, 14);
+    const START_16X30_BUTTON: Position = Position::from_xy(158, 14);
+    const START_16X30_WITH_WORMHOLE_BUTTON: Position = Position::from_xy(182, 14);
+    const START_DAILY_BUTTON: Position = Position::from_xy(198, 14);
+    const START_CUSTOM_BUTTON: Position = Position::from_xy(196, 14);
+
+    fn setup(qs: &str) -> Result<(Game, MockSystem)> {
+        let mut system = MockSystem::new();
+        let mut game = Game::default();
+        game.initialize(&mut system).or_fail()?;
+        game.command(&mut system, "setQueryString", qs.as_bytes())
+            .or_fail()?;
+        system.push_event(Event::WindowResized(game.fixed_window.size()));
+        system.dispatch(&mut game).or_fail()?;
+        Ok((game, system))
+    }
+
+    fn cell_pixel_position(cell: Position) -> Position {
+        Position::from_xy(3 + cell.x * 16 + 8, 30 + cell.y * 16 + 8)
+    }
+
+    #[test]
+    fn render_each_level() -> Result<()> {
+        let levels = [
+            ("small", "?seed=1", START_8X15_BUTTON),
+            ("large", "?seed=1", START_16X30_BUTTON),
+            ("wormhole", "?seed=1", START_16X30_WITH_WORMHOLE_BUTTON),
+            (
+                "custom",
+                "?seed=1&width=20&height=18&mines=40&wormholes=30",
+                START_CUSTOM_BUTTON,
+            ),
+            (
+                "wrap",
+                "?seed=1&width=16&height=16&mines=40&wormholes=20&wrap=both&ghost=true",
+                START_CUSTOM_BUTTON,
+            ),
+            (
+                "extended",
+                "?seed=1&width=16&height=16&mines=120&wormholes=0&neighborhood=extended",
+                START_CUSTOM_BUTTON,
+            ),
+            (
+                "anti",
+                "?seed=1&width=16&height=16&mines=40&wormholes=20&antimines=15",
+                START_CUSTOM_BUTTON,
+            ),
+            (
+                "double",
+                "?seed=1&width=16&height=16&mines=200&wormholes=0&doublemines=80",
+                START_CUSTOM_BUTTON,
+            ),
+            (
+                "clueless",
+                "?seed=1&width=20&height=18&mines=40&wormholes=30&wormholemode=clueless",
+                START_CUSTOM_BUTTON,
+            ),
+            (
+                "fog",
+                "?seed=1&width=16&height=16&mines=40&wormholes=20&fog=true",
+                START_CUSTOM_BUTTON,
+            ),
+            (
+                "hex",
+                "?seed=1&width=16&height=16&mines=40&wormholes=20&topology=hex",
+                START_CUSTOM_BUTTON,
+            ),
+        ];
+        for (name, qs, button) in levels {
+            let (mut game, mut system) = setup(qs).or_fail()?;
+            system.click(button);
+            system.dispatch(&mut game).or_fail()?;
+            assert!(matches!(game.model.state(), State::Playing), "{name}");
+
+            let cell = game.model.surrounding_mines().next().or_fail()?.0;
+            system.click(cell_pixel_position(cell.move_x(1).move_y(1)));
+            system.advance(Duration::from_secs(12));
+            system.dispatch(&mut game).or_fail()?;
+
+            assert_golden_frame(name, system.frame().or_fail()?).or_fail()?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn win_by_clicking_deduced_mines() -> Result<()> {
+        let (mut game, mut system) = setup("?seed=3").or_fail()?;
+        system.click(START_8X15_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+
+        let solutions = game.model.solver().solutions(1);
+        let mines = solutions.first().or_fail()?;
+        assert_eq!(mines.len(), 15);
+        game.model.show_hint();
+        assert_eq!(game.query(&mut system, "hintsUsed").or_fail()?, b"1");
+        for (i, &mine) in mines.iter().enumerate() {
+            assert_eq!(game.model.state(), State::Playing);
+            system.advance(Duration::from_secs(1));
+            system.click(cell_pixel_position(mine));
+            system.dispatch(&mut game).or_fail()?;
+            assert_eq!(game.model.remaining_mines(), 15 - i - 1);
+        }
+        assert_eq!(
+            game.model.state(),
+            State::Won {
+                elapsed_time: Duration::from_secs(15)
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn long_press_marks_safe_cell() -> Result<()> {
+        let (mut game, mut system) = setup("?seed=3").or_fail()?;
+        system.click(START_8X15_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+
+        let cell = game.model.solver().cells()[0];
+        let position = cell_pixel_position(cell);
+        let long_press = |game: &mut Game, system: &mut MockSystem| -> Result<()> {
+            system.push_event(Event::Mouse(MouseEvent::Down { position }));
+            system.dispatch(game).or_fail()?;
+            system.advance(Duration::from_millis(600));
+            system.push_event(Event::Mouse(MouseEvent::Up { position }));
+            system.dispatch(game).or_fail()
+        };
+
+        long_press(&mut game, &mut system).or_fail()?;
+        assert!(game.model.is_safe(cell));
+        assert!(!game.model.has_mine(cell));
+        assert_eq!(game.model.remaining_mines(), 15);
+
+        // Safe cells can't hold a mine.
+        system.click(position);
+        system.dispatch(&mut game).or_fail()?;
+        assert!(!game.model.has_mine(cell));
+
+        long_press(&mut game, &mut system).or_fail()?;
+        assert!(!game.model.is_safe(cell));
+
+        // A short press places a mine as usual.
+        system.click(position);
+        system.advance(Duration::from_millis(600));
+        system.dispatch(&mut game).or_fail()?;
+        assert!(game.model.has_mine(cell));
+        assert!(!game.model.is_safe(cell));
+        Ok(())
+    }
+
+    #[test]
+    fn daily_challenge() -> Result<()> {
+        // The daily board does not depend on the `seed` parameter.
+        let (mut game, mut system) = setup("?seed=1").or_fail()?;
+        system.click(START_DAILY_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+        assert_eq!(game.model.seed(), Some(20231114));
+        assert_eq!(game.model.solver().count_solutions(2), 1);
+
+        let mines = game.model.solver().solutions(1).pop().or_fail()?;
+        for record_secs in [30, 20, 25] {
+            system.click(START_DAILY_BUTTON);
+            system.dispatch(&mut game).or_fail()?;
+            system.advance(Duration::from_secs(record_secs));
+            for &mine in &mines {
+                system.click(cell_pixel_position(mine));
+            }
+            system.dispatch(&mut game).or_fail()?;
+            assert!(matches!(game.model.state(), State::Won { .. }));
+        }
+        assert_eq!(
+            game.query(&mut system, "dailyRecord").or_fail()?,
+            br#"{"date":{"year":2023,"month":11,"day":14},"best_time":{"secs":20,"nanos":0}}"#
+        );
+
+        // The record is kept along with the statistics, not the saved game.
+        let record = game.query(&mut system, "dailyRecord").or_fail()?;
+        let stats = game.query(&mut system, "saveStats").or_fail()?;
+        let state = game.query(&mut system, "saveState").or_fail()?;
+        let (mut game, mut system) = setup("").or_fail()?;
+        game.command(&mut system, "loadState", &state).or_fail()?;
+        assert_eq!(game.query(&mut system, "dailyRecord").or_fail()?, b"null");
+        game.command(&mut system, "loadStats", &stats).or_fail()?;
+        assert_eq!(game.query(&mut system, "dailyRecord").or_fail()?, record);
+        Ok(())
+    }
+
+    #[test]
+    fn replay_or_new_board_after_winning() -> Result<()> {
+        const REPLAY_BUTTON: Position = Position::from_xy(114, 299);
+        const NEW_BOARD_BUTTON: Position = Position::from_xy(148, 299);
+
+        let (mut game, mut system) = setup("?seed=3").or_fail()?;
+        system.click(START_8X15_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+        let mines = game.model.solver().solutions(1).pop().or_fail()?;
+        system.click(cell_pixel_position(mines[0]));
+        system.click(cell_pixel_position(mines[0]));
+        system.advance(Duration::from_secs(42));
+        for &mine in &mines {
+            system.click(cell_pixel_position(mine));
+        }
+        system.dispatch(&mut game).or_fail()?;
+        assert_eq!(game.model.toggles(), 17);
+        system.advance(Duration::from_millis(100));
+        system.dispatch(&mut game).or_fail()?;
+        assert_golden_frame("won", system.frame().or_fail()?).or_fail()?;
+
+        system.click(REPLAY_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+        assert_eq!(game.model.state(), State::Playing);
+        assert_eq!(game.model.seed(), Some(3));
+        assert_eq!(game.model.toggles(), 0);
+
+        for &mine in &mines {
+            system.click(cell_pixel_position(mine));
+        }
+        system.click(NEW_BOARD_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+        assert_eq!(game.model.state(), State::Playing);
+        assert_ne!(game.model.seed(), Some(3));
+        assert_eq!(game.model.level(), Level::Small);
+        Ok(())
+    }
+
+    #[test]
+    fn record_and_play_replay() -> Result<()> {
+        let (mut game, mut system) = setup("?seed=3").or_fail()?;
+        system.click(START_8X15_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+        let mines = game.model.solver().solutions(1).pop().or_fail()?;
+        for &mine in &mines {
+            system.advance(Duration::from_secs(1));
+            system.click(cell_pixel_position(mine));
+            system.dispatch(&mut game).or_fail()?;
+        }
+        let replay = game.query(&mut system, "replay").or_fail()?;
+        let won = game.model.state();
+        assert_eq!(
+            won,
+            State::Won {
+                elapsed_time: Duration::from_secs(15)
+            }
+        );
+
+        let (mut game, mut system) = setup("").or_fail()?;
+        let params = format!(
+            r#"{{"speed":2,"replay":{}}}"#,
+            String::from_utf8(replay).or_fail()?
+        );
+        game.command(&mut system, "playReplay", params.as_bytes())
+            .or_fail()?;
+        assert_eq!(game.model.level(), Level::Small);
+        assert_eq!(game.model.remaining_mines(), 15);
+
+        system.advance(Duration::from_secs(4));
+        system.dispatch(&mut game).or_fail()?;
+        assert_eq!(game.model.state(), State::Playing);
+        assert_eq!(game.model.remaining_mines(), 7);
+        assert_eq!(game.model.elapsed_time(), Duration::from_secs(8));
+
+        system.advance(Duration::from_secs(4));
+        system.dispatch(&mut game).or_fail()?;
+        assert_eq!(game.model.state(), won);
+        assert!(game.stats.level(Level::Small).is_none());
+
+        // The finished game can be watched again.
+        game.command(&mut system, "playReplay", br#"{"speed":4}"#)
+            .or_fail()?;
+        assert_eq!(game.model.state(), State::Playing);
+        system.advance(Duration::from_secs(4));
+        system.dispatch(&mut game).or_fail()?;
+        assert_eq!(game.model.state(), won);
+
+        assert!(game
+            .command(&mut system, "playReplay", br#"{"speed":3}"#)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn undo_and_redo_commands() -> Result<()> {
+        let (mut game, mut system) = setup("?seed=3").or_fail()?;
+        system.click(START_8X15_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+        let mines = game.model.solver().solutions(1).pop().or_fail()?;
+        for &mine in &mines {
+            system.advance(Duration::from_secs(1));
+            system.click(cell_pixel_position(mine));
+            system.dispatch(&mut game).or_fail()?;
+            if mine == mines[0] {
+                system.advance(Duration::from_secs(1));
+                game.command(&mut system, "undo", b"").or_fail()?;
+                system.advance(Duration::from_secs(1));
+                game.command(&mut system, "redo", b"").or_fail()?;
+            }
+        }
+
+        // The commands are recorded at the time they are issued, not at the last event before them.
+        let times = game
+            .model
+            .replay()
+            .events
+            .iter()
+            .map(|event| event.time.as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(&times[..4], &[1, 2, 3, 4]);
+        assert_eq!(
+            game.model.state(),
+            State::Won {
+                elapsed_time: Duration::from_secs(17)
+            }
+        );
+        assert_eq!(game.stats.level(Level::Small).or_fail()?.won, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn take_over_replayed_game() -> Result<()> {
+        let (mut game, mut system) = setup("?seed=3").or_fail()?;
+        system.click(START_8X15_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+        let mines = game.model.solver().solutions(1).pop().or_fail()?;
+        for &mine in &mines[..14] {
+            system.advance(Duration::from_secs(1));
+            system.click(cell_pixel_position(mine));
+            system.dispatch(&mut game).or_fail()?;
+        }
+        let unfinished = game.query(&mut system, "replay").or_fail()?;
+        let started = |game: &Game| game.stats.level(Level::Small).map(|s| (s.started, s.won));
+        assert_eq!(started(&game), Some((1, 0)));
+
+        // Interrupted by a click.
+        game.command(&mut system, "playReplay", br#"{"speed":4}"#)
+            .or_fail()?;
+        system.push_event(Event::WindowResized(game.fixed_window.size()));
+        system.advance(Duration::from_secs(2));
+        system.dispatch(&mut game).or_fail()?;
+        assert_eq!(game.model.remaining_mines(), 7);
+        for &mine in &mines[8..] {
+            system.click(cell_pixel_position(mine));
+        }
+        system.dispatch(&mut game).or_fail()?;
+        assert!(game.playback.is_none());
+        assert_eq!(
+            game.model.state(),
+            State::Won {
+                elapsed_time: Duration::from_secs(8)
+            }
+        );
+        assert_eq!(started(&game), Some((2, 1)));
+
+        // Played back to the end.
+        let params = format!(
+            r#"{{"speed":4,"replay":{}}}"#,
+            String::from_utf8(unfinished).or_fail()?
+        );
+        game.command(&mut system, "playReplay", params.as_bytes())
+            .or_fail()?;
+        system.push_event(Event::WindowResized(game.fixed_window.size()));
+        system.advance(Duration::from_secs(4));
+        system.dispatch(&mut game).or_fail()?;
+        assert!(game.playback.is_none());
+        assert_eq!(game.model.remaining_mines(), 1);
+        assert_eq!(started(&game), Some((3, 1)));
+
+        system.click(cell_pixel_position(mines[14]));
+        system.dispatch(&mut game).or_fail()?;
+        assert!(matches!(game.model.state(), State::Won { .. }));
+        assert_eq!(started(&game), Some((3, 2)));
+
+        // Loading a saved game ends the playback.
+        let state = game.query(&mut system, "saveState").or_fail()?;
+        game.command(&mut system, "playReplay", br#"{"speed":1}"#)
+            .or_fail()?;
+        game.command(&mut system, "loadState", &state).or_fail()?;
+        assert!(game.playback.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn failure_to_start_keeps_current_game() -> Result<()> {
+        let (mut game, mut system) = setup("?seed=3").or_fail()?;
+        system.click(START_8X15_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+        let mines = game.model.solver().solutions(1).pop().or_fail()?;
+        system.click(cell_pixel_position(mines[0]));
+        system.dispatch(&mut game).or_fail()?;
+        let state = game.query(&mut system, "saveState").or_fail()?;
+
+        // No board of this level has a unique solution.
+        game.model.set_custom_level(Level::Custom {
+            width: 2,
+            height: 1,
+            mines: 1,
+            wormholes: 0,
+            rules: Default::default(),
+        });
+        game.command(&mut system, "loadState", &state).or_fail()?;
+        game.model.set_unique(true);
+        system.push_event(Event::WindowResized(game.fixed_window.size()));
+        system.push_event(Event::Timeout(tag::START_8X15_TIMEOUT));
+        system.dispatch(&mut game).or_fail()?;
+
+        assert_eq!(game.model.level(), Level::Small);
+        assert_eq!(game.model.state(), State::Playing);
+        assert_eq!(game.model.remaining_mines(), 14);
+        assert_eq!(game.stats.levels().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn replay_and_saved_game_keep_query_string_level() -> Result<()> {
+        const CUSTOM: &str = "?seed=1&width=20&height=18&mines=40&wormholes=30";
+        let custom = Level::from_qs(CUSTOM).or_fail()?.level.or_fail()?;
+        let play = |game: &mut Game, system: &mut MockSystem, replay: &[u8]| {
+            let params = format!(
+                r#"{{"speed":1,"replay":{}}}"#,
+                std::str::from_utf8(replay).or_fail()?
+            );
+            game.command(system, "playReplay", params.as_bytes())
+                .or_fail()?;
+            system.push_event(Event::WindowResized(game.fixed_window.size()));
+            system.dispatch(game)
+        };
+
+        let (mut game, mut system) = setup("?seed=3&level=small").or_fail()?;
+        let small_replay = game.query(&mut system, "replay").or_fail()?;
+        let small_state = game.query(&mut system, "saveState").or_fail()?;
+        let (mut game, mut system) = setup(CUSTOM).or_fail()?;
+        system.click(START_CUSTOM_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+        let custom_replay = game.query(&mut system, "replay").or_fail()?;
+
+        // A replay of a normal level in the custom mode.
+        play(&mut game, &mut system, &small_replay).or_fail()?;
+        assert_eq!(game.model.level(), Level::Small);
+        assert!(game.model.is_custom_mode());
+        system.click(START_CUSTOM_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+        assert_eq!(game.model.level(), custom);
+        assert_eq!(
+            game.fixed_window.size(),
+            FixedWindow::new(game.window.window_size(&game.model)).size()
+        );
+
+        // The same goes for a saved game.
+        game.command(&mut system, "loadState", &small_state)
+            .or_fail()?;
+        assert_eq!(game.model.level(), Level::Small);
+        assert!(game.model.is_custom_mode());
+
+        // A replay of a custom level in the normal mode.
+        let (mut game, mut system) = setup("").or_fail()?;
+        play(&mut game, &mut system, &custom_replay).or_fail()?;
+        assert_eq!(game.model.level(), custom);
+        assert!(!game.model.is_custom_mode());
+        system.click(START_8X15_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+        assert_eq!(game.model.level(), Level::Small);
+        Ok(())
+    }
+
+    #[test]
+    fn query_screenshot() -> Result<()> {
+        let (mut game, mut system) = setup("?seed=1").or_fail()?;
+        system.click(START_8X15_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+        system.advance(Duration::from_secs(1));
+        system.dispatch(&mut game).or_fail()?;
+        let frame = system.frame().or_fail()?;
+        let (size, expected) = (frame.spec().resolution, frame.data().to_vec());
+
+        let decode = |png: Vec<u8>| -> Result<(png::OutputInfo, Vec<u8>)> {
+            let mut reader = png::Decoder::new(png.as_slice()).read_info().or_fail()?;
+            let mut buf = vec![0; reader.output_buffer_size()];
+            let info = reader.next_frame(&mut buf).or_fail()?;
+            buf.truncate(info.buffer_size());
+            Ok((info, buf))
+        };
+
+        let (info, data) = decode(game.query(&mut system, "screenshot").or_fail()?).or_fail()?;
+        assert_eq!((info.width, info.height), (size.width, size.height));
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        assert!(data == expected);
+
+        let (info, data) = decode(game.query(&mut system, "screenshot:3").or_fail()?).or_fail()?;
+        assert_eq!((info.width, info.height), (size.width * 3, size.height * 3));
+        for (y, row) in data.chunks(info.width as usize * 3).enumerate() {
+            for (x, pixel) in row.chunks(3).enumerate() {
+                let i = (y / 3 * size.width as usize + x / 3) * 3;
+                assert_eq!(pixel, &expected[i..i + 3]);
+            }
+        }
+
+        assert!(game.query(&mut system, "screenshot:0").is_err());
+        assert!(game.query(&mut system, "screenshot:x").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn query_replay_animation() -> Result<()> {
+        let (mut game, mut system) = setup("?seed=3").or_fail()?;
+        system.click(START_8X15_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+        for mine in game.model.solver().solutions(1).pop().or_fail()? {
+            system.advance(Duration::from_secs(1));
+            system.click(cell_pixel_position(mine));
+            system.dispatch(&mut game).or_fail()?;
+        }
+        let won = game.model.state();
+        let screenshot = game.query(&mut system, "screenshot:2").or_fail()?;
+
+        let apng = game.query(&mut system, "replayAnimation:2:2").or_fail()?;
+        assert_eq!(game.model.state(), won);
+
+        // A frame per second (the timer and the placed mines) plus the initial frame.
+        let mut reader = png::Decoder::new(apng.as_slice()).read_info().or_fail()?;
+        let animation = reader.info().animation_control().or_fail()?;
+        assert_eq!(animation.num_frames, 16);
+        let mut frame = vec![0; reader.output_buffer_size()];
+        for _ in 0..animation.num_frames {
+            reader.next_frame(&mut frame).or_fail()?;
+        }
+        let control = reader.info().frame_control().or_fail()?;
+        assert_eq!((control.delay_num, control.delay_den), (5, 2));
+
+        let mut reader = png::Decoder::new(screenshot.as_slice())
+            .read_info()
+            .or_fail()?;
+        let mut expected = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut expected).or_fail()?;
+        assert!(frame == expected);
+
+        assert!(game.query(&mut system, "replayAnimation:0").is_err());
+        Ok(())
+    }
+
+    // Places the mines of a solution after `time` has passed.
+    fn solve_board(game: &mut Game, system: &mut MockSystem, time: Duration) -> Result<()> {
+        system.advance(time);
+        for mine in game.model.solver().solutions(1).pop().or_fail()? {
+            system.click(cell_pixel_position(mine));
+        }
+        system.dispatch(game).or_fail()?;
+        assert!(matches!(game.model.state(), State::Won { .. }));
+        Ok(())
+    }
+
+    fn session_summary(game: &mut Game, system: &mut MockSystem) -> Result<Option<SessionSummary>> {
+        let summary = game.query(system, "sessionSummary").or_fail()?;
+        serde_json::from_slice(&summary).or_fail()
+    }
+
+    #[test]
+    fn time_attack_session() -> Result<()> {
+        let (mut game, mut system) = setup("?seed=3&timeattack=1").or_fail()?;
+        assert_eq!(game.model.level(), Level::Small);
+        assert_eq!(game.model.seed(), Some(3));
+
+        for _ in 0..2 {
+            solve_board(&mut game, &mut system, Duration::from_secs(20)).or_fail()?;
+            system.advance(NEXT_BOARD_TIMEOUT_DURATION);
+            system.dispatch(&mut game).or_fail()?;
+            assert_eq!(game.model.state(), State::Playing);
+            assert_eq!(game.model.level(), Level::Small);
+        }
+        let summary = session_summary(&mut game, &mut system)
+            .or_fail()?
+            .or_fail()?;
+        assert_eq!((summary.solved, summary.finished), (2, false));
+
+        // The board being played when the time is up is not counted.
+        system.advance(Duration::from_secs(18));
+        system.dispatch(&mut game).or_fail()?;
+        let summary = session_summary(&mut game, &mut system)
+            .or_fail()?
+            .or_fail()?;
+        assert_eq!((summary.solved, summary.finished), (2, true));
+        assert_eq!(summary.total_time, Duration::from_secs(40));
+        assert_golden_frame("time_attack_summary", system.frame().or_fail()?).or_fail()?;
+
+        system.click(Position::from_xy(0, 0));
+        system.dispatch(&mut game).or_fail()?;
+        assert_eq!(session_summary(&mut game, &mut system).or_fail()?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn marathon_session() -> Result<()> {
+        let (mut game, mut system) = setup("?seed=3&marathon=true").or_fail()?;
+        for (i, level) in MARATHON_LEVELS.into_iter().enumerate() {
+            assert_eq!(game.model.level(), level);
+            let time = Duration::from_secs(10 * (i as u64 + 1));
+            solve_board(&mut game, &mut system, time).or_fail()?;
+            system.advance(NEXT_BOARD_TIMEOUT_DURATION);
+            system.dispatch(&mut game).or_fail()?;
+        }
+        let summary = session_summary(&mut game, &mut system)
+            .or_fail()?
+            .or_fail()?;
+        assert_eq!((summary.solved, summary.finished), (3, true));
+        assert_eq!(summary.total_time, Duration::from_secs(60));
+        assert_eq!(game.model.level(), Level::LargeWithWormhole);
+        assert_eq!(game.stats.level(Level::Large).or_fail()?.won, 1);
+
+        // Starting another game abandons the session.
+        let (mut game, mut system) = setup("?seed=3&marathon=true").or_fail()?;
+        system.click(START_16X30_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+        assert_eq!(session_summary(&mut game, &mut system).or_fail()?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn save_and_load_stats() -> Result<()> {
+        let (mut game, mut system) = setup("?seed=3").or_fail()?;
+        system.click(START_8X15_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+        system.advance(Duration::from_secs(5));
+        for mine in game.model.solver().solutions(1).pop().or_fail()? {
+            system.click(cell_pixel_position(mine));
+        }
+        system.click(START_16X30_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+
+        let stats = game.query(&mut system, "saveStats").or_fail()?;
+        let (mut game, mut system) = setup("").or_fail()?;
+        game.command(&mut system, "loadStats", &stats).or_fail()?;
+        let small = game.stats.level(Level::Small).or_fail()?;
+        assert_eq!((small.started, small.won), (1, 1));
+        assert_eq!(small.best_time, Some(Duration::from_secs(5)));
+        let large = game.stats.level(Level::Large).or_fail()?;
+        assert_eq!((large.started, large.won), (1, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn load_stats_before_query_string() -> Result<()> {
+        let (mut game, mut system) = setup("?level=small").or_fail()?;
+        let stats = game.query(&mut system, "saveStats").or_fail()?;
+
+        // Same order as the front ends: `loadStats` and then `setQueryString`.
+        let mut system = MockSystem::new();
+        let mut game = Game::default();
+        game.initialize(&mut system).or_fail()?;
+        game.command(&mut system, "loadStats", &stats).or_fail()?;
+        game.command(&mut system, "setQueryString", b"?level=small")
+            .or_fail()?;
+        let small = game.stats.level(Level::Small).or_fail()?;
+        assert_eq!(small.started, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn query_seed() -> Result<()> {
+        let (mut game, mut system) = setup("?seed=42").or_fail()?;
+        assert_eq!(game.query(&mut system, "seed").or_fail()?, b"null");
+
+        system.click(START_16X30_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+        assert_eq!(game.query(&mut system, "seed").or_fail()?, b"42");
+        Ok(())
+    }
+
+    #[test]
+    fn query_difficulty() -> Result<()> {
+        let (mut game, mut system) = setup("?seed=1").or_fail()?;
+        system.click(START_16X30_WITH_WORMHOLE_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+
+        let difficulty: Difficulty =
+            serde_json::from_slice(&game.query(&mut system, "difficulty").or_fail()?).or_fail()?;
+        assert_eq!(difficulty, game.model.solver().difficulty());
+        assert!((1..=5).contains(&difficulty.rating));
+        Ok(())
+    }
+
+    #[test]
+    fn save_and_load_state() -> Result<()> {
+        let (mut game, mut system) = setup("?seed=7").or_fail()?;
+        system.click(START_16X30_WITH_WORMHOLE_BUTTON);
+        system.dispatch(&mut game).or_fail()?;
+        let cell = game.model.solver().cells()[0];
+        system.click(cell_pixel_position(cell));
+        system.advance(Duration::from_secs(30));
+        system.dispatch(&mut game).or_fail()?;
+        let state = game.query(&mut system, "saveState").or_fail()?;
+
+        let (mut resumed, mut system) = setup("").or_fail()?;
+        resumed
+            .command(&mut system, "loadState", &state)
+            .or_fail()?;
+        system.advance(Duration::from_secs(5));
+        system.dispatch(&mut resumed).or_fail()?;
+        assert_eq!(resumed.model.seed(), Some(7));
+        assert!(resumed.model.has_mine(cell));
+        assert_eq!(resumed.model.elapsed_time(), Duration::from_secs(35));
+
+        assert!(resumed.command(&mut system, "loadState", b"{}").is_err());
+
+        // A tampered counter is recomputed, and an out-of-range history is rejected.
+        let mut tampered: serde_json::Value = serde_json::from_slice(&state).or_fail()?;
+        tampered["remaining_mines"] = 0.into();
+        let data = serde_json::to_vec(&tampered).or_fail()?;
+        resumed.command(&mut system, "loadState", &data).or_fail()?;
+        assert_eq!(resumed.model.remaining_mines(), 98);
+
+        tampered["undo_history"][0]["position"] = serde_json::json!({"x": 100, "y": 100});
+        let data = serde_json::to_vec(&tampered).or_fail()?;
+        assert!(resumed.command(&mut system, "loadState", &data).is_err());
+        assert!(resumed.model.has_mine(cell));
+        resumed.command(&mut system, "undo", b"").or_fail()?;
+        assert!(!resumed.model.has_mine(cell));
+        Ok(())
+    }
+
+    #[test]
+    fn query_string_game_takes_precedence_over_saved_game() -> Result<()> {
+        let (mut game, mut system) = setup("?seed=7&level=large").or_fail()?;
+        let state = game.query(&mut system, "saveState").or_fail()?;
+
+        let (mut game, mut system) = setup("?seed=3&timeattack=1").or_fail()?;
+        game.command(&mut system, "loadState", &state).or_fail()?;
+        assert!(session_summary(&mut game, &mut system).or_fail()?.is_some());
+        assert_eq!(game.model.level(), Level::Small);
+        assert_eq!(game.model.seed(), Some(3));
+
+        let (mut game, mut system) = setup("?seed=3&level=wormhole").or_fail()?;
+        game.command(&mut system, "loadState", &state).or_fail()?;
+        assert_eq!(game.model.level(), Level::LargeWithWormhole);
+        Ok(())
+    }
+}
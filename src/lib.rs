@@ -1,5 +1,12 @@
 pub mod asset;
+pub mod export;
 pub mod game;
+#[cfg(test)]
+mod mock;
 pub mod model;
+pub mod replay;
+pub mod session;
+pub mod solver;
+pub mod stats;
 pub mod tag;
 pub mod view;
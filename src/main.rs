@@ -1,16 +1,139 @@
-use orfail::OrFail;
+use mineplacer::model::Level;
+use orfail::{Failure, OrFail};
 use pagurus::event::{Event, Key, KeyEvent};
 use pagurus::Game;
 use pagurus_tui::TuiSystem;
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const STATE_FILE: &str = "mineplacer-state.json";
+const STATS_FILE: &str = "mineplacer-stats.json";
+
+const USAGE: &str = "Usage: mineplacer [OPTIONS]
+
+Options:
+  --level <NAME>     Start a game immediately (small, large, wormhole, daily or custom)
+  --width <N>        Board width of the custom level (16..=64)
+  --height <N>       Board height of the custom level (16..=64)
+  --mines <N>        Number of mines of the custom level (1..=999)
+  --wormholes <N>    Number of wormholes of the custom level (0..=999)
+  --topology <NAME>  Cell shape of the custom level (square or hex)
+  --neighborhood <NAME>
+                     Cells counted by each digit of the custom level (moore, cross, extended or knight)
+  --wrap <EDGES>     Edges of the custom level that wrap around (none, horizontal, vertical or both)
+  --antimines <N>    Number of the mines of the custom level that count as -1 (0..=mines)
+  --doublemines <N>  Number of the cells of the custom level that hold two mines
+  --wormholemode <NAME>
+                     Behavior of the wormholes of the custom level (hole or clueless)
+  --fog              Hide the clues of the custom level until mines are placed around them
+  --ghost            Draw the cells on the opposite edges around wrapping boards
+  --timeattack <MINUTES>
+                     Solve as many 8x15 boards as possible within the time limit (1..=15)
+  --marathon         Solve the 8x15, 16x30 and 16x30-with-wormholes boards in a row
+  --seed <N>         Seed of the first generated board
+  --unique           Generate only boards that have a unique solution
+  --log <PATH>       Log file path [default: mineplacer.log]
+  --resume           Resume the game saved on the last exit
+  --screenshot <PATH>
+                     Save a PNG screenshot of the board to PATH on exit
+  --screenshot-scale <N>
+                     Magnification of the screenshot (1..=8) [default: 1]
+  -h, --help         Print this help";
+
+#[derive(Debug)]
+struct Args {
+    query_string: String,
+    log_file: PathBuf,
+    resume: bool,
+    screenshot: Option<PathBuf>,
+    screenshot_scale: u32,
+}
+
+impl Args {
+    fn parse() -> pagurus::Result<Option<Self>> {
+        let mut params = Vec::new();
+        let mut log_file = PathBuf::from("mineplacer.log");
+        let mut resume = false;
+        let mut screenshot = None;
+        let mut screenshot_scale = 1;
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .or_fail_with(|_| format!("missing value for '{arg}'"))
+            };
+            match arg.as_str() {
+                "--level" | "--width" | "--height" | "--mines" | "--wormholes" | "--topology"
+                | "--neighborhood" | "--wrap" | "--antimines" | "--doublemines"
+                | "--wormholemode" | "--timeattack" | "--seed" => {
+                    params.push(format!("{}={}", &arg[2..], value()?));
+                }
+                "--unique" => params.push("unique=true".to_owned()),
+                "--fog" => params.push("fog=true".to_owned()),
+                "--marathon" => params.push("marathon=true".to_owned()),
+                "--ghost" => params.push("ghost=true".to_owned()),
+                "--log" => log_file = PathBuf::from(value()?),
+                "--resume" => resume = true,
+                "--screenshot" => screenshot = Some(PathBuf::from(value()?)),
+                "--screenshot-scale" => {
+                    screenshot_scale = value()?
+                        .parse::<u32>()
+                        .or_fail_with(|e| format!("invalid value for '{arg}': {e}"))?;
+                }
+                "-h" | "--help" => return Ok(None),
+                _ => return Err(Failure::new(format!("unknown option: '{arg}'\n\n{USAGE}"))),
+            }
+        }
+
+        // The options are validated in the same way as the query string of the web version.
+        let query_string = format!("?{}", params.join("&"));
+        // `--level`, `--timeattack` and `--marathon` start a new game, which would be replaced.
+        let params = Level::from_qs(&query_string).or_fail()?;
+        (!resume || (params.start.is_none() && params.session.is_none())).or_fail_with(|_| {
+            "'--resume' can't be combined with '--level', '--timeattack' or '--marathon'".to_owned()
+        })?;
+        (!resume || Path::new(STATE_FILE).exists())
+            .or_fail_with(|_| format!("no saved game found: {STATE_FILE}"))?;
+        (1..=mineplacer::export::MAX_SCALE)
+            .contains(&screenshot_scale)
+            .or_fail_with(|_| format!("invalid screenshot scale: {screenshot_scale}"))?;
+
+        Ok(Some(Self {
+            query_string,
+            log_file,
+            resume,
+            screenshot,
+            screenshot_scale,
+        }))
+    }
+}
 
 fn main() -> pagurus::Result<()> {
-    pagurus::io::set_println_fn(file_println).or_fail()?;
+    let Some(args) = Args::parse().or_fail()? else {
+        println!("{USAGE}");
+        return Ok(());
+    };
+    let log_file = args.log_file.clone();
+    pagurus::io::set_println_fn(move |msg| file_println(&log_file, msg)).or_fail()?;
 
     let mut system = TuiSystem::new().or_fail()?;
     let mut game = mineplacer::game::Game::default();
     game.initialize(&mut system).or_fail()?;
+    // Statistics are loaded first, so that a game started by the options is counted.
+    if Path::new(STATS_FILE).exists() {
+        let stats = std::fs::read(STATS_FILE).or_fail()?;
+        if let Err(e) = game.command(&mut system, "loadStats", &stats) {
+            pagurus::println!("failed to load the statistics: {e}");
+        }
+    }
+    game.command(&mut system, "setQueryString", args.query_string.as_bytes())
+        .or_fail()?;
+    if args.resume {
+        let state = std::fs::read(STATE_FILE).or_fail()?;
+        game.command(&mut system, "loadState", &state).or_fail()?;
+    }
     while let Ok(event) = system.next_event() {
         if matches!(event, Event::Key(KeyEvent { key: Key::Esc, .. })) {
             break;
@@ -19,13 +142,23 @@ fn main() -> pagurus::Result<()> {
             break;
         }
     }
+
+    let state = game.query(&mut system, "saveState").or_fail()?;
+    std::fs::write(STATE_FILE, state).or_fail()?;
+    let stats = game.query(&mut system, "saveStats").or_fail()?;
+    std::fs::write(STATS_FILE, stats).or_fail()?;
+    if let Some(path) = &args.screenshot {
+        let query = format!("screenshot:{}", args.screenshot_scale);
+        let png = game.query(&mut system, &query).or_fail()?;
+        std::fs::write(path, png).or_fail()?;
+    }
     Ok(())
 }
 
-fn file_println(msg: &str) {
+fn file_println(path: &Path, msg: &str) {
     let _ = OpenOptions::new()
         .create(true)
         .append(true)
-        .open("mineplacer.log")
+        .open(path)
         .and_then(|mut file| writeln!(file, "{}", msg));
 }
@@ -0,0 +1,153 @@
+use orfail::OrFail;
+use pagurus::{
+    audio::{AudioData, AudioSpec, SampleFormat},
+    event::{Event, MouseEvent, TimeoutTag},
+    spatial::{Position, Size},
+    video::{PixelFormat, VideoFrame, VideoFrameSpec},
+    Game, Result, System,
+};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Deterministic [`System`] for headless tests.
+///
+/// The clock only advances via [`MockSystem::advance()`], events are scripted beforehand
+/// and the last drawn video frame is captured.
+#[derive(Debug, Default)]
+pub struct MockSystem {
+    game_time: Duration,
+    unix_time: Duration,
+    timeouts: Vec<(Duration, TimeoutTag)>,
+    events: VecDeque<Event>,
+    frame: Option<VideoFrame>,
+}
+
+impl MockSystem {
+    pub fn new() -> Self {
+        Self {
+            unix_time: Duration::from_secs(1_700_000_000),
+            ..Self::default()
+        }
+    }
+
+    pub fn push_event(&mut self, event: Event) {
+        self.events.push_back(event);
+    }
+
+    pub fn click(&mut self, position: Position) {
+        self.push_event(Event::Mouse(MouseEvent::Down { position }));
+        self.push_event(Event::Mouse(MouseEvent::Up { position }));
+    }
+
+    pub fn advance(&mut self, duration: Duration) {
+        self.game_time += duration;
+        self.unix_time += duration;
+    }
+
+    /// Feeds the expired timeouts and the scripted events to `game` until none remain.
+    pub fn dispatch<G: Game<Self>>(&mut self, game: &mut G) -> Result<()> {
+        loop {
+            let event = if let Some(tag) = self.pop_expired_timeout() {
+                Event::Timeout(tag)
+            } else if let Some(event) = self.events.pop_front() {
+                event
+            } else {
+                return Ok(());
+            };
+            game.handle_event(self, event).or_fail()?;
+        }
+    }
+
+    pub fn frame(&self) -> Option<&VideoFrame> {
+        self.frame.as_ref()
+    }
+
+    fn pop_expired_timeout(&mut self) -> Option<TimeoutTag> {
+        let i = (0..self.timeouts.len())
+            .filter(|&i| self.timeouts[i].0 <= self.game_time)
+            .min_by_key(|&i| self.timeouts[i])?;
+        Some(self.timeouts.remove(i).1)
+    }
+}
+
+impl System for MockSystem {
+    fn video_init(&mut self, resolution: Size) -> VideoFrameSpec {
+        VideoFrameSpec {
+            pixel_format: PixelFormat::Rgb24,
+            resolution,
+            stride: resolution.width,
+        }
+    }
+
+    fn video_draw(&mut self, frame: VideoFrame<&[u8]>) {
+        let data = frame.data().to_owned();
+        self.frame = VideoFrame::with_data(frame.spec(), data).ok();
+    }
+
+    fn audio_init(&mut self, sample_rate: u16, data_samples: usize) -> AudioSpec {
+        AudioSpec {
+            sample_format: SampleFormat::I16Be,
+            sample_rate,
+            data_samples,
+        }
+    }
+
+    fn audio_enqueue(&mut self, _data: AudioData<&[u8]>) {}
+
+    fn clock_game_time(&self) -> Duration {
+        self.game_time
+    }
+
+    fn clock_unix_time(&self) -> Duration {
+        self.unix_time
+    }
+
+    fn clock_set_timeout(&mut self, tag: TimeoutTag, timeout: Duration) {
+        self.timeouts.push((self.game_time + timeout, tag));
+    }
+}
+
+/// Compares an RGB24 frame with the PNG image at `testdata/golden/{name}.png`.
+///
+/// Set the `UPDATE_GOLDEN` environment variable to (re)generate the image instead.
+pub fn assert_golden_frame(name: &str, frame: &VideoFrame) -> Result<()> {
+    let spec = frame.spec();
+    (spec.pixel_format == PixelFormat::Rgb24 && spec.stride == spec.resolution.width).or_fail()?;
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("testdata/golden")
+        .join(format!("{name}.png"));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().or_fail()?).or_fail()?;
+        let file = std::fs::File::create(&path).or_fail()?;
+        let mut encoder = png::Encoder::new(file, spec.resolution.width, spec.resolution.height);
+        encoder.set_color(png::ColorType::Rgb);
+        let mut writer = encoder.write_header().or_fail()?;
+        writer.write_image_data(frame.data()).or_fail()?;
+        return Ok(());
+    }
+
+    let file = std::fs::File::open(&path)
+        .or_fail_with(|e| format!("cannot open {}: {e}", path.display()))?;
+    let mut reader = png::Decoder::new(file).read_info().or_fail()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).or_fail()?;
+    let size = Size::from_wh(info.width, info.height);
+    (size == spec.resolution).or_fail_with(|_| {
+        format!(
+            "frame size mismatch: expected {size:?}, actual {:?}",
+            spec.resolution
+        )
+    })?;
+    (info.color_type == png::ColorType::Rgb).or_fail()?;
+
+    let expected = &buf[..info.buffer_size()];
+    let diff = expected
+        .chunks(3)
+        .zip(frame.data().chunks(3))
+        .filter(|(a, b)| a != b)
+        .count();
+    (diff == 0).or_fail_with(|_| format!("{diff} pixels differ from {}", path.display()))?;
+    Ok(())
+}
@@ -1,40 +1,235 @@
-use orfail::OrFail;
+use crate::replay::{Replay, ReplayAction, ReplayEvent};
+use crate::session::SessionMode;
+use crate::solver::{Difficulty, Solver, Step};
+use orfail::{Failure, OrFail};
 use pagurus::{
     random::StdRng,
     spatial::{Contains, Position, Region, Size},
     Result, System,
 };
-use rand::seq::SliceRandom;
+use rand::{seq::SliceRandom, Rng};
 use std::time::Duration;
 
 const WIDTH: usize = 16;
 const HEIGHT: usize = 30;
+const MAX_UNIQUE_ATTEMPTS: usize = 1000;
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum Level {
     Small,
     #[default]
     Large,
     LargeWithWormhole,
+    Daily,
     Custom {
         width: usize,
         height: usize,
         mines: usize,
         wormholes: usize,
+        #[serde(default)]
+        rules: Rules,
     },
 }
 
+/// Optional rules of custom levels, which change how clues are counted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Rules {
+    #[serde(default)]
+    pub topology: Topology,
+
+    /// Neighborhood of clues on square boards (hexagonal boards always count the six adjacent cells).
+    #[serde(default)]
+    pub neighborhood: Neighborhood,
+
+    /// Whether the left and right edges are adjacent to each other.
+    #[serde(default)]
+    pub wrap_x: bool,
+
+    /// Whether the top and bottom edges are adjacent to each other.
+    #[serde(default)]
+    pub wrap_y: bool,
+
+    /// Number of the mines that count as -1 toward clues (included in the number of mines).
+    #[serde(default)]
+    pub anti_mines: usize,
+
+    /// Number of the cells that hold two mines (each counted twice in the number of mines).
+    #[serde(default)]
+    pub double_mines: usize,
+
+    #[serde(default)]
+    pub wormhole_mode: WormholeMode,
+
+    /// Whether clues are hidden until a mine is placed within their range (fog of war).
+    ///
+    /// Only the clues around a random starting cell are visible at first.
+    /// Hints are disabled as they would be based on the hidden clues.
+    #[serde(default)]
+    pub fog: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Topology {
+    #[default]
+    Square,
+
+    /// Hexagonal cells, where odd rows are shifted right by half a cell.
+    Hex,
+}
+
+/// How wormhole cells behave.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum WormholeMode {
+    /// Holes in the board, which are not drawn and can't hold mines.
+    #[default]
+    Hole,
+
+    /// Cells that are drawn and may hold mines like others, but show no clues.
+    Clueless,
+}
+
+/// Cells counted by a clue on square boards, in addition to the clue cell itself.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Neighborhood {
+    /// The eight surrounding cells.
+    #[default]
+    Moore,
+
+    /// The four orthogonally adjacent cells (von Neumann neighborhood).
+    Cross,
+
+    /// The cells within two steps in each direction (5x5).
+    Extended,
+
+    /// The eight cells a knight's move away.
+    Knight,
+}
+
+impl Neighborhood {
+    /// Returns the relative positions of the cells that a clue counts (including itself).
+    pub fn deltas(self) -> &'static [(i32, i32)] {
+        match self {
+            Neighborhood::Moore => &[
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (0, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ],
+            Neighborhood::Cross => &[(0, -1), (-1, 0), (0, 0), (1, 0), (0, 1)],
+            Neighborhood::Extended => &[
+                (-2, -2),
+                (-1, -2),
+                (0, -2),
+                (1, -2),
+                (2, -2),
+                (-2, -1),
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (2, -1),
+                (-2, 0),
+                (-1, 0),
+                (0, 0),
+                (1, 0),
+                (2, 0),
+                (-2, 1),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+                (2, 1),
+                (-2, 2),
+                (-1, 2),
+                (0, 2),
+                (1, 2),
+                (2, 2),
+            ],
+            Neighborhood::Knight => &[
+                (-1, -2),
+                (1, -2),
+                (-2, -1),
+                (2, -1),
+                (0, 0),
+                (-2, 1),
+                (2, 1),
+                (-1, 2),
+                (1, 2),
+            ],
+        }
+    }
+}
+
+impl Rules {
+    /// Returns `true` if some mines are anti-mines.
+    pub fn has_anti_mines(self) -> bool {
+        self.anti_mines > 0
+    }
+
+    /// Returns `true` if some cells hold two mines.
+    pub fn has_double_mines(self) -> bool {
+        self.double_mines > 0
+    }
+
+    /// Returns `true` if every mine is a normal one, that is, each cell has only two states.
+    ///
+    /// [`Solver`] can't handle the other boards, so their hints, difficulty rating and
+    /// unique-solution mode are disabled.
+    pub fn is_binary(self) -> bool {
+        !self.has_anti_mines() && !self.has_double_mines()
+    }
+
+    // Returns the kinds of mines that a click cycles through, in order.
+    fn mine_kinds(self) -> Vec<MineKind> {
+        let mut kinds = vec![MineKind::Normal];
+        if self.has_double_mines() {
+            kinds.push(MineKind::Double);
+        }
+        if self.has_anti_mines() {
+            kinds.push(MineKind::Anti);
+        }
+        kinds
+    }
+
+    // Returns the relative positions of the cells that a clue at row `y` counts (including itself).
+    fn neighborhood(self, y: i32) -> &'static [(i32, i32)] {
+        match self.topology {
+            Topology::Square => self.neighborhood.deltas(),
+            Topology::Hex if y.rem_euclid(2) == 0 => {
+                &[(-1, -1), (0, -1), (-1, 0), (0, 0), (1, 0), (-1, 1), (0, 1)]
+            }
+            Topology::Hex => &[(0, -1), (1, -1), (-1, 0), (0, 0), (1, 0), (0, 1), (1, 1)],
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct QueryParams {
+    pub level: Option<Level>,
+    pub start: Option<Level>,
+    pub seed: Option<u32>,
+    pub unique: bool,
+    pub ghost_border: bool,
+    pub session: Option<SessionMode>,
+}
+
 impl Level {
-    pub fn from_qs(qs: &str) -> Result<Option<Self>> {
+    pub fn from_qs(qs: &str) -> Result<QueryParams> {
+        let mut params = QueryParams::default();
         if !qs.starts_with('?') {
-            return Ok(None);
+            return Ok(params);
         }
 
         let mut width: usize = 16;
         let mut height: usize = 30;
         let mut mines: usize = 99;
         let mut wormholes: usize = 99;
+        let mut rules = Rules::default();
         let mut custom = false;
+        let mut level_name = None;
 
         for kv in qs[1..].split('&') {
             match kv.splitn(2, '=').collect::<Vec<_>>().as_slice() {
@@ -79,56 +274,224 @@ impl Level {
                         })?;
                     custom = true;
                 }
+                ["topology", v] => {
+                    rules.topology = match *v {
+                        "square" => Some(Topology::Square),
+                        "hex" => Some(Topology::Hex),
+                        _ => None,
+                    }
+                    .or_fail_with(|_| {
+                        "'topology' parameter should be 'square' or 'hex'".to_owned()
+                    })?;
+                    custom = true;
+                }
+                ["wrap", v] => {
+                    (rules.wrap_x, rules.wrap_y) = match *v {
+                        "none" => Some((false, false)),
+                        "horizontal" => Some((true, false)),
+                        "vertical" => Some((false, true)),
+                        "both" => Some((true, true)),
+                        _ => None,
+                    }
+                    .or_fail_with(|_| {
+                        "'wrap' parameter should be one of 'none', 'horizontal', 'vertical' or 'both'"
+                            .to_owned()
+                    })?;
+                    custom = true;
+                }
+                ["neighborhood", v] => {
+                    rules.neighborhood = match *v {
+                        "moore" => Some(Neighborhood::Moore),
+                        "cross" => Some(Neighborhood::Cross),
+                        "extended" => Some(Neighborhood::Extended),
+                        "knight" => Some(Neighborhood::Knight),
+                        _ => None,
+                    }
+                    .or_fail_with(|_| {
+                        "'neighborhood' parameter should be one of 'moore', 'cross', 'extended' or 'knight'"
+                            .to_owned()
+                    })?;
+                    custom = true;
+                }
+                ["antimines", v] => {
+                    rules.anti_mines = v
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|v| (0..=999).contains(v))
+                        .or_fail_with(|_| {
+                            "'antimines' parameter should be a integer between 0 and 1000"
+                                .to_owned()
+                        })?;
+                    custom = true;
+                }
+                ["doublemines", v] => {
+                    rules.double_mines = v
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|v| (0..=999).contains(v))
+                        .or_fail_with(|_| {
+                            "'doublemines' parameter should be a integer between 0 and 1000"
+                                .to_owned()
+                        })?;
+                    custom = true;
+                }
+                ["wormholemode", v] => {
+                    rules.wormhole_mode = match *v {
+                        "hole" => Some(WormholeMode::Hole),
+                        "clueless" => Some(WormholeMode::Clueless),
+                        _ => None,
+                    }
+                    .or_fail_with(|_| {
+                        "'wormholemode' parameter should be 'hole' or 'clueless'".to_owned()
+                    })?;
+                    custom = true;
+                }
+                ["fog", v] => {
+                    rules.fog = v.parse::<bool>().ok().or_fail_with(|_| {
+                        "'fog' parameter should be 'true' or 'false'".to_owned()
+                    })?;
+                    custom = true;
+                }
+                ["ghost", v] => {
+                    params.ghost_border = v.parse::<bool>().ok().or_fail_with(|_| {
+                        "'ghost' parameter should be 'true' or 'false'".to_owned()
+                    })?;
+                }
+                ["level", v] => {
+                    level_name = Some(*v);
+                    custom |= *v == "custom";
+                }
+                ["seed", v] => {
+                    params.seed = Some(v.parse::<u32>().ok().or_fail_with(|_| {
+                        "'seed' parameter should be a integer between 0 and 4294967295".to_owned()
+                    })?);
+                }
+                ["timeattack", v] => {
+                    let minutes = v
+                        .parse::<u64>()
+                        .ok()
+                        .filter(|v| (1..=15).contains(v))
+                        .or_fail_with(|_| {
+                            "'timeattack' parameter should be a integer between 1 and 15".to_owned()
+                        })?;
+                    params.session = Some(SessionMode::TimeAttack {
+                        time_limit: Duration::from_secs(minutes * 60),
+                    });
+                }
+                ["marathon", v] => {
+                    let marathon = v.parse::<bool>().ok().or_fail_with(|_| {
+                        "'marathon' parameter should be 'true' or 'false'".to_owned()
+                    })?;
+                    if marathon {
+                        params.session = Some(SessionMode::Marathon);
+                    }
+                }
+                ["unique", v] => {
+                    params.unique = v.parse::<bool>().ok().or_fail_with(|_| {
+                        "'unique' parameter should be 'true' or 'false'".to_owned()
+                    })?;
+                }
                 _ => {}
             }
         }
         if !custom {
-            return Ok(None);
+            params.start = level_name.map(Self::from_name).transpose().or_fail()?;
+            return Ok(params);
         }
+        params.session.is_none().or_fail_with(|_| {
+            "Time-attack and marathon modes are not available with custom parameters".to_owned()
+        })?;
+        matches!(level_name, None | Some("custom")).or_fail_with(|_| {
+            "Only 'custom' can be specified as the 'level' parameter with custom parameters"
+                .to_owned()
+        })?;
+
+        (rules.topology == Topology::Square || rules.neighborhood == Neighborhood::Moore)
+            .or_fail_with(|_| {
+                "'neighborhood' parameter is only available on square boards".to_owned()
+            })?;
+
+        (rules.anti_mines + 2 * rules.double_mines <= mines).or_fail_with(|_| {
+            "Anti-mines and double mines should not outnumber the mines".to_owned()
+        })?;
+
+        // Keeps clues between -9 and 9, which have sprites.
+        (!rules.has_anti_mines() || rules.neighborhood != Neighborhood::Extended).or_fail_with(
+            |_| "Anti-mines are not available with the 'extended' neighborhood".to_owned(),
+        )?;
+
+        // Otherwise, the rows at the top and bottom edges would be shifted in the same direction.
+        (rules.topology != Topology::Hex || !rules.wrap_y || height.is_multiple_of(2))
+            .or_fail_with(|_| {
+                "Hexagonal boards can wrap vertically only if the height is even".to_owned()
+            })?;
 
         let cells = width * height;
-        (mines + wormholes <= cells).or_fail_with(|_| "Too many mines and wormholes".to_owned())?;
+        (mines - rules.double_mines + wormholes <= cells)
+            .or_fail_with(|_| "Too many mines and wormholes".to_owned())?;
 
-        Ok(Some(Self::Custom {
+        let level = Self::Custom {
             width,
             height,
             mines,
             wormholes,
-        }))
+            rules,
+        };
+        params.level = Some(level);
+        params.start = level_name.map(|_| level);
+        Ok(params)
+    }
+
+    fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "small" => Some(Level::Small),
+            "large" => Some(Level::Large),
+            "wormhole" => Some(Level::LargeWithWormhole),
+            "daily" => Some(Level::Daily),
+            _ => None,
+        }
+        .or_fail_with(|_| {
+            "'level' parameter should be one of 'small', 'large', 'wormhole', 'daily' or 'custom'"
+                .to_owned()
+        })
     }
 
-    fn mines(self) -> usize {
+    pub fn mines(self) -> usize {
         match self {
             Level::Small => 15,
             Level::Large => 99,
             Level::LargeWithWormhole => 99,
+            Level::Daily => 99,
             Level::Custom { mines, .. } => mines,
         }
     }
 
-    fn wormholes(self) -> usize {
+    pub fn wormholes(self) -> usize {
         match self {
             Level::Small => 0,
             Level::Large => 0,
             Level::LargeWithWormhole => 99,
+            Level::Daily => 99,
             Level::Custom { wormholes, .. } => wormholes,
         }
     }
 
-    fn width(self) -> usize {
+    pub fn width(self) -> usize {
         match self {
             Level::Small => 8,
             Level::Large => 16,
             Level::LargeWithWormhole => 16,
+            Level::Daily => 16,
             Level::Custom { width, .. } => width,
         }
     }
 
-    fn height(self) -> usize {
+    pub fn height(self) -> usize {
         match self {
             Level::Small => 15,
             Level::Large => 30,
             Level::LargeWithWormhole => 30,
+            Level::Daily => 30,
             Level::Custom { height, .. } => height,
         }
     }
@@ -138,10 +501,28 @@ impl Level {
             Level::Small => Position::from_xy(4, 7),
             Level::Large => Position::from_xy(0, 0),
             Level::LargeWithWormhole => Position::from_xy(0, 0),
+            Level::Daily => Position::from_xy(0, 0),
             Level::Custom { .. } => Position::from_xy(0, 0),
         }
     }
 
+    // Checks the parameters of a level that did not come from `Level::from_qs()`.
+    fn is_valid(self) -> bool {
+        let (width, height) = (self.width(), self.height());
+        let rules = self.rules();
+        (1..=64).contains(&width)
+            && (1..=64).contains(&height)
+            && rules.anti_mines + 2 * rules.double_mines <= self.mines()
+            && self.mines() - rules.double_mines + self.wormholes() <= width * height
+    }
+
+    pub fn rules(self) -> Rules {
+        match self {
+            Level::Custom { rules, .. } => rules,
+            _ => Rules::default(),
+        }
+    }
+
     pub fn is_custom(self) -> bool {
         matches!(self, Level::Custom { .. })
     }
@@ -155,7 +536,58 @@ impl Level {
     }
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+/// Calendar date in UTC, which identifies the board of the daily challenge.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct Date {
+    pub year: u32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    pub fn from_unix_time(unix_time: Duration) -> Self {
+        // Converts the days since 1970-01-01 into a date of the Gregorian calendar.
+        // See: https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = unix_time.as_secs() / 86_400 + 719_468;
+        let era = z / 146_097;
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + u64::from(month <= 2);
+        Self {
+            year: year as u32,
+            month: month as u32,
+            day: day as u32,
+        }
+    }
+
+    /// Returns the seed of the daily challenge board (e.g., `20230415` for 2023-04-15).
+    pub fn seed(self) -> u32 {
+        self.year * 10_000 + self.month * 100 + self.day
+    }
+}
+
+impl std::fmt::Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum State {
     #[default]
     Initial,
@@ -165,20 +597,50 @@ pub enum State {
     },
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Model {
+    #[serde(skip)]
     rng: StdRng,
+    seed: Option<u32>,
+    #[serde(skip)]
+    next_seed: Option<u32>,
+    unique: bool,
     board: Board,
     remaining_mines: usize,
+    #[serde(skip)]
     start_time: Duration,
+    #[serde(skip)]
+    resumed_elapsed_time: Duration,
     elapsed_time: Duration,
     level: Level,
+
+    // Level given by the query string, which every new game is started with.
+    // It is kept apart from `level` as a replay or a saved game may be of another level.
+    #[serde(skip)]
+    custom_level: Option<Level>,
+
     state: State,
+    #[serde(skip)]
+    hint: Option<Step>,
+    hints_used: usize,
+    #[serde(default)]
+    toggles: usize,
+    #[serde(default)]
+    difficulty: Difficulty,
+    #[serde(skip)]
+    today: Date,
+    #[serde(default)]
+    daily_date: Option<Date>,
+    #[serde(default)]
+    replay: Replay,
+    undo_history: Vec<Toggle>,
+    redo_history: Vec<Toggle>,
 }
 
 impl Model {
     pub fn initialize<S: System>(&mut self, system: &mut S) -> Result<()> {
         self.rng = StdRng::from_clock_seed(system.clock_unix_time());
+        self.update_today(system);
         self.board
             .set_board_size(Size::from_wh(WIDTH as u32, HEIGHT as u32));
         Ok(())
@@ -187,32 +649,103 @@ impl Model {
     pub fn set_custom_level(&mut self, level: Level) {
         self.board.set_board_size(level.board_size());
         self.level = level;
+        self.custom_level = Some(level);
+    }
+
+    pub fn set_next_seed(&mut self, seed: u32) {
+        self.next_seed = Some(seed);
+    }
+
+    pub fn set_unique(&mut self, unique: bool) {
+        self.unique = unique;
     }
 
     pub fn start_game<S: System>(&mut self, system: &mut S, mut level: Level) -> Result<()> {
-        if self.level.is_custom() {
-            level = self.level;
+        if let Some(custom_level) = self.custom_level {
+            level = custom_level;
         }
-        self.level = level;
-        self.board = Board::default();
-        self.board.set_board_size(level.board_size());
-        self.board.region = Region::new(
+
+        // Everyone gets the same board on the same day, regardless of the query string.
+        self.daily_date = None;
+        if level == Level::Daily {
+            self.update_today(system);
+            self.daily_date = Some(self.today);
+        }
+
+        let seed = match self.daily_date {
+            Some(date) => date.seed(),
+            None => self.next_seed.take().unwrap_or_else(|| self.rng.gen()),
+        };
+        let unique = self.unique || self.daily_date.is_some();
+        self.start(system, level, seed, unique).or_fail()?;
+        Ok(())
+    }
+
+    /// Starts the game recorded in `replay` (without its events) to play it back.
+    pub fn start_replay<S: System>(&mut self, system: &mut S, replay: &Replay) -> Result<()> {
+        replay
+            .level
+            .is_valid()
+            .or_fail_with(|_| format!("Invalid replay level: {:?}", replay.level))?;
+
+        // A replay is not a challenge of the day, even if it is of the daily level.
+        self.daily_date = None;
+        self.start(system, replay.level, replay.seed, replay.unique)
+            .or_fail()?;
+        Ok(())
+    }
+
+    fn start<S: System>(
+        &mut self,
+        system: &mut S,
+        level: Level,
+        seed: u32,
+        unique: bool,
+    ) -> Result<()> {
+        // The board is generated before anything is changed, so that a failure leaves the current game as is.
+        let mut board = Board::default();
+        board.set_board_size(level.board_size());
+        board.rules = level.rules();
+        board.region = Region::new(
             level.offset(),
             Size::from_wh(level.width() as u32, level.height() as u32),
         );
 
-        let mut mines = self.board.region.iter().collect::<Vec<_>>();
-        mines.shuffle(&mut self.rng);
-        for p in &mines[..level.wormholes()] {
-            self.board.cells[p.y as usize][p.x as usize].wormhole = true;
+        let mut rng = StdRng::from_clock_seed(Duration::from_micros(u64::from(seed)));
+        board.place_mines(&mut rng, level);
+        let binary = level.rules().is_binary();
+        if unique && binary {
+            board.make_unique(&mut rng, level).or_fail()?;
         }
-        for p in &mines[level.wormholes()..][..level.mines()] {
-            self.board.cells[p.y as usize][p.x as usize].expected_mine = true;
+        self.level = level;
+        self.board = board;
+        self.seed = Some(seed);
+
+        self.difficulty = if binary {
+            self.solver().difficulty()
+        } else {
+            Difficulty::default()
+        };
+        if level.rules().fog {
+            self.board.reveal_start(&mut rng);
         }
 
         self.start_time = system.clock_game_time();
+        self.resumed_elapsed_time = Duration::ZERO;
+        self.elapsed_time = Duration::ZERO;
         self.remaining_mines = level.mines();
         self.state = State::Playing;
+        self.hint = None;
+        self.hints_used = 0;
+        self.toggles = 0;
+        self.undo_history.clear();
+        self.redo_history.clear();
+        self.replay = Replay {
+            level,
+            seed,
+            unique,
+            events: Vec::new(),
+        };
         Ok(())
     }
 
@@ -220,6 +753,22 @@ impl Model {
         self.level.board_size()
     }
 
+    /// Returns the region of the cells in play (smaller than [`Model::board_size()`] for the 8x15 level).
+    pub fn board_region(&self) -> Region {
+        self.board.region
+    }
+
+    /// Makes a solver from the clues of the current board (the player's placement is not taken into account).
+    ///
+    /// The solver is not meaningful on non-binary boards (see [`Rules::is_binary()`]).
+    pub fn solver(&self) -> Solver {
+        self.board.solver(self.mines())
+    }
+
+    pub fn seed(&self) -> Option<u32> {
+        self.seed
+    }
+
     pub fn wormholes(&self) -> usize {
         self.level.wormholes()
     }
@@ -228,8 +777,13 @@ impl Model {
         self.level.mines()
     }
 
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Returns `true` if the query string specifies a custom level (see [`Model::set_custom_level()`]).
     pub fn is_custom_mode(&self) -> bool {
-        self.level.is_custom()
+        self.custom_level.is_some()
     }
 
     pub fn state(&self) -> State {
@@ -240,18 +794,134 @@ impl Model {
         self.board.cells[p.y as usize][p.x as usize].wormhole
     }
 
+    /// Returns `true` if the clue of the cell is visible (see [`Rules::fog`]).
+    pub fn is_revealed(&self, p: Position) -> bool {
+        !self.board.rules.fog || self.board.cell(p).revealed
+    }
+
+    /// Returns `true` if the cell is a wormhole that is a hole in the board (see [`WormholeMode`]).
+    pub fn is_hole(&self, p: Position) -> bool {
+        self.board.is_hole(p)
+    }
+
     pub fn remaining_mines(&self) -> usize {
         self.remaining_mines
     }
 
+    /// Finds the first logical deduction that the current placement does not follow yet.
+    pub fn show_hint(&mut self) {
+        let rules = self.level.rules();
+        if self.state != State::Playing || !rules.is_binary() || rules.fog {
+            return;
+        }
+
+        let solver = self.solver();
+        self.hint = solver
+            .steps()
+            .find(|step| self.has_mine(step.position) != step.mine);
+        if self.hint.is_some() {
+            self.hints_used += 1;
+        }
+    }
+
+    pub fn hint(&self) -> Option<&Step> {
+        self.hint.as_ref()
+    }
+
+    pub fn hints_used(&self) -> usize {
+        self.hints_used
+    }
+
+    /// Returns the number of mines placed or removed in the current game, including undo and redo.
+    pub fn toggles(&self) -> usize {
+        self.toggles
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    /// Replaces the current game with a saved one (see `Game::query("saveState")`),
+    /// resuming its timer from the saved elapsed time.
+    pub fn restore<S: System>(&mut self, system: &S, mut saved: Model) -> Result<()> {
+        let size = saved.level.board_size();
+        (saved.board.cells.len() == size.height as usize
+            && saved
+                .board
+                .cells
+                .iter()
+                .all(|row| row.len() == size.width as usize))
+        .or_fail_with(|_| "Saved board size does not match its level".to_owned())?;
+        (size.to_region().intersection(saved.board.region) == saved.board.region)
+            .or_fail_with(|_| "Saved board region is out of range".to_owned())?;
+        (saved.board.rules == saved.level.rules())
+            .or_fail_with(|_| "Saved board rules do not match its level".to_owned())?;
+        let mines = saved.level.mines();
+        saved
+            .undo_history
+            .iter()
+            .chain(&saved.redo_history)
+            .all(|t| saved.board.region.contains(&t.position) && t.remaining_mines <= mines)
+            .or_fail_with(|_| "Saved undo/redo history is out of range".to_owned())?;
+
+        // The counter is derived from the board rather than trusted.
+        let placed = saved
+            .board
+            .region
+            .iter()
+            .map(|p| saved.board.cell(p))
+            .filter(|cell| cell.actual_mine)
+            .map(|cell| cell.actual_kind.count())
+            .sum::<usize>();
+        saved.remaining_mines = mines
+            .checked_sub(placed)
+            .or_fail_with(|_| "Saved board has too many mines".to_owned())?;
+
+        saved.rng = std::mem::take(&mut self.rng);
+        saved.next_seed = self.next_seed;
+        saved.custom_level = self.custom_level;
+        saved.today = self.today;
+        saved.start_time = system.clock_game_time();
+        saved.resumed_elapsed_time = saved.elapsed_time;
+        *self = saved;
+        Ok(())
+    }
+
+    pub fn update_today<S: System>(&mut self, system: &S) {
+        self.today = Date::from_unix_time(system.clock_unix_time());
+    }
+
+    /// Returns the current UTC date (as of the last [`Model::update_today()`] call).
+    pub fn today(&self) -> Date {
+        self.today
+    }
+
+    /// Returns the date of the daily challenge being played, if any.
+    pub fn daily_date(&self) -> Option<Date> {
+        self.daily_date
+    }
+
     pub fn update_elapsed_time<S: System>(&mut self, system: &S) {
-        self.elapsed_time = system.clock_game_time() - self.start_time;
+        self.elapsed_time =
+            self.resumed_elapsed_time + (system.clock_game_time() - self.start_time);
+    }
+
+    /// Sets the elapsed time directly (e.g., during playback), from which the timer continues.
+    pub fn set_elapsed_time<S: System>(&mut self, system: &S, elapsed_time: Duration) {
+        self.start_time = system.clock_game_time();
+        self.resumed_elapsed_time = elapsed_time;
+        self.elapsed_time = elapsed_time;
     }
 
     pub fn elapsed_time(&self) -> Duration {
         self.elapsed_time
     }
 
+    /// Moves a position beyond an edge that wraps around into the board.
+    pub fn wrap_position(&self, p: Position) -> Position {
+        self.board.wrap(p)
+    }
+
     pub fn surrounding_mines(&self) -> impl '_ + Iterator<Item = (Position, isize)> {
         self.board
             .region
@@ -266,23 +936,66 @@ impl Model {
         if !self.board.region.contains(&position) {
             return;
         }
-        if self.has_wormhole(position) {
+        if self.is_hole(position) {
             return;
         }
 
-        let cell = &mut self.board.cells[position.y as usize][position.x as usize];
-
-        if !cell.actual_mine && self.remaining_mines == 0 {
+        let cell = self.board.cell(position);
+        if cell.safe || (!cell.actual_mine && self.remaining_mines == 0) {
             return;
         }
 
-        cell.actual_mine = !cell.actual_mine;
-        if cell.actual_mine {
-            self.remaining_mines -= 1;
-        } else {
-            self.remaining_mines += 1;
+        self.undo_history.push(Toggle {
+            position,
+            previous_mine: cell.actual_mine,
+            previous_kind: cell.actual_kind,
+            remaining_mines: self.remaining_mines,
+        });
+        self.redo_history.clear();
+        self.toggle(position);
+        self.record(ReplayAction::Click(position));
+        self.check_won();
+    }
+
+    fn record(&mut self, action: ReplayAction) {
+        self.replay.events.push(ReplayEvent {
+            time: self.elapsed_time,
+            action,
+        });
+    }
+
+    /// Returns the record of the current game.
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// Applies a recorded event as if it happened at the recorded time.
+    pub fn apply_replay_event<S: System>(&mut self, system: &S, event: ReplayEvent) {
+        self.set_elapsed_time(system, event.time);
+        match event.action {
+            ReplayAction::Click(position) => self.handle_click(position),
+            ReplayAction::Undo => self.undo(),
+            ReplayAction::Redo => self.redo(),
         }
+    }
 
+    /// Toggles the "no mine here" mark of a cell, which is only a note for the player.
+    pub fn handle_long_press(&mut self, position: Position) {
+        if self.state != State::Playing {
+            return;
+        }
+        if !self.board.region.contains(&position) {
+            return;
+        }
+        if self.is_hole(position) || self.has_mine(position) {
+            return;
+        }
+
+        let cell = self.board.cell_mut(position);
+        cell.safe = !cell.safe;
+    }
+
+    fn check_won(&mut self) {
         if self.remaining_mines == 0
             && self
                 .surrounding_mines()
@@ -291,42 +1004,282 @@ impl Model {
         {
             self.state = State::Won {
                 elapsed_time: self.elapsed_time(),
+            };
+        }
+    }
+
+    // Cycles a cell through no mine, a mine and the other kinds of mines of the level
+    // (see [`Rules::mine_kinds()`]), skipping kinds that need more mines than remaining.
+    fn toggle(&mut self, position: Position) {
+        self.toggles += 1;
+        let kinds = self.level.rules().mine_kinds();
+        let current = self.mine_kind(position);
+        let available = self.remaining_mines + current.map_or(0, MineKind::count);
+        let start = current.map_or(0, |kind| {
+            kinds
+                .iter()
+                .position(|&k| k == kind)
+                .map_or(kinds.len(), |i| i + 1)
+        });
+        let next = kinds[start..]
+            .iter()
+            .copied()
+            .find(|kind| kind.count() <= available);
+
+        let cell = self.board.cell_mut(position);
+        cell.actual_mine = next.is_some();
+        if let Some(kind) = next {
+            cell.actual_kind = kind;
+            if self.board.rules.fog {
+                self.board.reveal(position);
             }
         }
+        self.remaining_mines = available - next.map_or(0, MineKind::count);
+        self.hint = None;
+    }
+
+    /// Reverts the last toggle (disabled once the game is won).
+    pub fn undo(&mut self) {
+        if self.state != State::Playing {
+            return;
+        }
+        let Some(&toggle) = self.undo_history.last() else {
+            return;
+        };
+        if self.is_safe(toggle.position) {
+            // The cell has been marked as safe since the toggle.
+            return;
+        }
+        self.undo_history.pop();
+        let cell = self.board.cell_mut(toggle.position);
+        cell.actual_mine = toggle.previous_mine;
+        cell.actual_kind = toggle.previous_kind;
+        self.remaining_mines = toggle.remaining_mines;
+        self.toggles += 1;
+        self.hint = None;
+        self.redo_history.push(toggle);
+        self.record(ReplayAction::Undo);
+        self.check_won();
+    }
+
+    /// Re-applies the last undone toggle (disabled once the game is won).
+    pub fn redo(&mut self) {
+        if self.state != State::Playing {
+            return;
+        }
+        let Some(&toggle) = self.redo_history.last() else {
+            return;
+        };
+        if self.is_safe(toggle.position) {
+            // The cell has been marked as safe since the toggle was undone.
+            return;
+        }
+        self.redo_history.pop();
+        self.toggle(toggle.position);
+        self.undo_history.push(toggle);
+        self.record(ReplayAction::Redo);
+        self.check_won();
     }
 
     pub fn has_mine(&self, p: Position) -> bool {
         self.board.cells[p.y as usize][p.x as usize].actual_mine
     }
+
+    /// Returns the kind of the mine placed by the player, if any.
+    pub fn mine_kind(&self, p: Position) -> Option<MineKind> {
+        let cell = self.board.cell(p);
+        cell.actual_mine.then_some(cell.actual_kind)
+    }
+
+    pub fn is_safe(&self, p: Position) -> bool {
+        self.board.cell(p).safe
+    }
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+struct Toggle {
+    position: Position,
+    previous_mine: bool,
+    #[serde(default)]
+    previous_kind: MineKind,
+    remaining_mines: usize,
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
 struct Board {
     cells: Vec<Vec<Cell>>,
     region: Region,
+    #[serde(default)]
+    rules: Rules,
 }
 
 impl Board {
+    fn cell(&self, p: Position) -> Cell {
+        self.cells[p.y as usize][p.x as usize]
+    }
+
+    fn cell_mut(&mut self, p: Position) -> &mut Cell {
+        &mut self.cells[p.y as usize][p.x as usize]
+    }
+
+    fn is_hole(&self, p: Position) -> bool {
+        self.cell(p).wormhole && self.rules.wormhole_mode == WormholeMode::Hole
+    }
+
+    fn neighbors(&self, p: Position) -> impl '_ + Iterator<Item = Position> {
+        self.rules
+            .neighborhood(p.y)
+            .iter()
+            .map(move |&(x_delta, y_delta)| self.wrap(p.move_x(x_delta).move_y(y_delta)))
+            .filter(|p| self.region.contains(p))
+    }
+
+    fn wrap(&self, mut p: Position) -> Position {
+        let (start, size) = (self.region.start(), self.region.size);
+        if self.rules.wrap_x {
+            p.x = start.x + (p.x - start.x).rem_euclid(size.width as i32);
+        }
+        if self.rules.wrap_y {
+            p.y = start.y + (p.y - start.y).rem_euclid(size.height as i32);
+        }
+        p
+    }
+
+    // Reveals the clues that count the cell, which stay visible even if the mine is removed.
+    fn reveal(&mut self, p: Position) {
+        for p in self.neighbors(p).collect::<Vec<_>>() {
+            self.cell_mut(p).revealed = true;
+        }
+    }
+
+    // Reveals the clues within two steps of a random cell.
+    fn reveal_start(&mut self, rng: &mut StdRng) {
+        let cells = self
+            .region
+            .iter()
+            .filter(|&p| !self.cell(p).wormhole)
+            .collect::<Vec<_>>();
+        let Some(&start) = cells.choose(rng) else {
+            return;
+        };
+        for p in self.neighbors(start).collect::<Vec<_>>() {
+            self.reveal(p);
+        }
+    }
+
+    fn clue(&self, p: Position) -> isize {
+        self.neighbors(p)
+            .map(|p| self.cell(p))
+            .filter(|cell| cell.expected_mine)
+            .map(|cell| cell.expected_kind.weight())
+            .sum()
+    }
+
     fn surrounding_mines(&self, p: Position) -> isize {
-        let mut expected: isize = 0;
-        let mut actual: isize = 0;
-        for y_delta in [-1, 0, 1] {
-            for x_delta in [-1, 0, 1] {
-                let p = p.move_y(y_delta).move_x(x_delta);
-                if !self.region.contains(&p) {
-                    continue;
-                }
+        let actual = self
+            .neighbors(p)
+            .map(|p| self.cell(p))
+            .filter(|cell| cell.actual_mine)
+            .map(|cell| cell.actual_kind.weight())
+            .sum::<isize>();
+        self.clue(p) - actual
+    }
+
+    // Repairs (or, if that does not help, regenerates) the layout until
+    // the clues determine a single mine placement.
+    //
+    // When two placements satisfy the clues, a wormhole that hides a clue around a
+    // differing cell is moved to somewhere else so that the clue becomes visible.
+    fn make_unique(&mut self, rng: &mut StdRng, level: Level) -> Result<()> {
+        for _ in 0..MAX_UNIQUE_ATTEMPTS {
+            let solutions = self.solver(level.mines()).solutions(2);
+            let [a, b] = solutions.as_slice() else {
+                return Ok(());
+            };
 
-                let cell = self.cells[p.y as usize][p.x as usize];
-                if cell.expected_mine {
-                    expected += 1;
+            let differing = a
+                .iter()
+                .filter(|p| !b.contains(p))
+                .chain(b.iter().filter(|p| !a.contains(p)))
+                .copied()
+                .collect::<Vec<_>>();
+            let wormholes = differing
+                .iter()
+                .flat_map(|&p| self.neighbors(p))
+                .filter(|&p| self.cell(p).wormhole)
+                .collect::<Vec<_>>();
+            let candidates = self
+                .region
+                .iter()
+                .filter(|&p| {
+                    let cell = self.cell(p);
+                    !cell.wormhole && !cell.expected_mine && !differing.contains(&p)
+                })
+                .collect::<Vec<_>>();
+            match (wormholes.choose(rng), candidates.choose(rng)) {
+                (Some(&from), Some(&to)) => {
+                    self.cell_mut(from).wormhole = false;
+                    self.cell_mut(to).wormhole = true;
                 }
-                if cell.actual_mine {
-                    actual += 1;
+                _ => {
+                    self.clear();
+                    self.place_mines(rng, level);
                 }
             }
         }
-        expected - actual
+
+        Err(Failure::new(format!(
+            "No board with a unique solution was found in {MAX_UNIQUE_ATTEMPTS} attempts: {level:?}"
+        )))
+    }
+
+    fn place_mines(&mut self, rng: &mut StdRng, level: Level) {
+        let mut cells = self.region.iter().collect::<Vec<_>>();
+        cells.shuffle(rng);
+        for p in &cells[..level.wormholes()] {
+            self.cell_mut(*p).wormhole = true;
+        }
+        let rules = level.rules();
+        let mut mines = cells.split_off(level.wormholes());
+        if rules.wormhole_mode == WormholeMode::Clueless {
+            // Clueless wormholes may hide mines as well.
+            mines.append(&mut cells);
+            mines.shuffle(rng);
+        }
+        for (i, p) in mines[..level.mines() - rules.double_mines]
+            .iter()
+            .enumerate()
+        {
+            let cell = self.cell_mut(*p);
+            cell.expected_mine = true;
+            if i < rules.anti_mines {
+                cell.expected_kind = MineKind::Anti;
+            } else if i < rules.anti_mines + rules.double_mines {
+                cell.expected_kind = MineKind::Double;
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        for p in self.region.iter() {
+            *self.cell_mut(p) = Cell::default();
+        }
+    }
+
+    fn solver(&self, mines: usize) -> Solver {
+        let cells = self
+            .region
+            .iter()
+            .filter(|&p| !self.is_hole(p))
+            .collect::<Vec<_>>();
+        let mut solver = Solver::new(mines);
+        for &p in &cells {
+            solver.add_cell(p);
+        }
+        for &p in cells.iter().filter(|&&p| !self.cell(p).wormhole) {
+            solver.add_clue(p, self.neighbors(p), self.clue(p));
+        }
+        solver
     }
 
     fn set_board_size(&mut self, size: Size) {
@@ -335,9 +1288,660 @@ impl Board {
     }
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, serde::Serialize, serde::Deserialize)]
 struct Cell {
     expected_mine: bool,
     actual_mine: bool,
     wormhole: bool,
+    #[serde(default)]
+    safe: bool,
+    #[serde(default)]
+    expected_kind: MineKind,
+    #[serde(default)]
+    actual_kind: MineKind,
+    #[serde(default)]
+    revealed: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum MineKind {
+    #[default]
+    Normal,
+
+    /// Counts as -1 toward clues.
+    Anti,
+
+    /// Two mines in a cell, which count as +2 toward clues.
+    Double,
+}
+
+impl MineKind {
+    fn weight(self) -> isize {
+        match self {
+            MineKind::Normal => 1,
+            MineKind::Anti => -1,
+            MineKind::Double => 2,
+        }
+    }
+
+    // Returns the number of mines of the level that the kind consumes.
+    fn count(self) -> usize {
+        match self {
+            MineKind::Normal | MineKind::Anti => 1,
+            MineKind::Double => 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockSystem;
+
+    fn start(level: Level, seed: u32) -> Result<Model> {
+        let mut system = MockSystem::new();
+        let mut model = Model::default();
+        model.initialize(&mut system).or_fail()?;
+        if level.is_custom() {
+            model.set_custom_level(level);
+        }
+        model.set_next_seed(seed);
+        model.start_game(&mut system, level).or_fail()?;
+        Ok(model)
+    }
+
+    fn expected_mines(model: &Model) -> Vec<Position> {
+        model
+            .board
+            .region
+            .iter()
+            .filter(|&p| model.board.cell(p).expected_mine)
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_yields_same_board() -> Result<()> {
+        let level = Level::LargeWithWormhole;
+        let a = start(level, 10).or_fail()?;
+        let b = start(level, 10).or_fail()?;
+        let c = start(level, 11).or_fail()?;
+        assert_eq!(a.seed(), Some(10));
+        assert_eq!(expected_mines(&a), expected_mines(&b));
+        assert_ne!(expected_mines(&a), expected_mines(&c));
+        assert_eq!(
+            a.surrounding_mines().collect::<Vec<_>>(),
+            b.surrounding_mines().collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unique_mode_yields_single_solution() -> Result<()> {
+        let mut system = MockSystem::new();
+        let mut model = Model::default();
+        model.initialize(&mut system).or_fail()?;
+        model.set_unique(true);
+        for seed in 0..5 {
+            model.set_next_seed(seed);
+            model
+                .start_game(&mut system, Level::LargeWithWormhole)
+                .or_fail()?;
+            assert_eq!(model.wormholes(), 99);
+            assert_eq!(model.solver().count_solutions(2), 1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn unique_mode_fails_without_unique_board() -> Result<()> {
+        let mut system = MockSystem::new();
+        let mut model = Model::default();
+        model.initialize(&mut system).or_fail()?;
+        model.set_unique(true);
+        model.start_game(&mut system, Level::Small).or_fail()?;
+
+        // Both cells see each other, so the mine can be in either of them.
+        let level = Level::Custom {
+            width: 2,
+            height: 1,
+            mines: 1,
+            wormholes: 0,
+            rules: Rules::default(),
+        };
+        assert!(model.start_game(&mut system, level).is_err());
+        assert_eq!(model.level(), Level::Small);
+        assert_eq!(model.state(), State::Playing);
+        Ok(())
+    }
+
+    #[test]
+    fn win_by_placing_expected_mines() -> Result<()> {
+        let mut model = start(Level::Small, 0).or_fail()?;
+        let mines = expected_mines(&model);
+        let (last, others) = mines.split_last().or_fail()?;
+
+        let wrong = model
+            .board
+            .region
+            .iter()
+            .find(|p| !mines.contains(p))
+            .or_fail()?;
+        for &p in others {
+            model.handle_click(p);
+        }
+        model.handle_click(wrong);
+        assert_eq!(model.remaining_mines(), 0);
+        assert_eq!(model.state(), State::Playing);
+
+        // No more mines can be placed.
+        model.handle_click(*last);
+        assert!(!model.has_mine(*last));
+
+        model.handle_click(wrong);
+        // Safe marks are ignored by the win check.
+        model.handle_long_press(wrong);
+        assert!(model.is_safe(wrong));
+        model.handle_click(*last);
+        assert!(matches!(model.state(), State::Won { .. }));
+
+        // The board is frozen after winning.
+        model.handle_click(*last);
+        assert!(model.has_mine(*last));
+        Ok(())
+    }
+
+    #[test]
+    fn hex_clues_count_six_neighbors() -> Result<()> {
+        let level = Level::from_qs("?width=16&height=16&mines=40&wormholes=0&topology=hex")
+            .or_fail()?
+            .level
+            .or_fail()?;
+        let mut model = start(level, 0).or_fail()?;
+        let neighbors = |p| model.board.neighbors(p).collect::<Vec<_>>();
+        let xy = Position::from_xy;
+        assert_eq!(
+            neighbors(xy(3, 2)),
+            [
+                xy(2, 1),
+                xy(3, 1),
+                xy(2, 2),
+                xy(3, 2),
+                xy(4, 2),
+                xy(2, 3),
+                xy(3, 3)
+            ]
+        );
+        assert_eq!(
+            neighbors(xy(3, 3)),
+            [
+                xy(3, 2),
+                xy(4, 2),
+                xy(2, 3),
+                xy(3, 3),
+                xy(4, 3),
+                xy(3, 4),
+                xy(4, 4)
+            ]
+        );
+        assert_eq!(
+            neighbors(xy(15, 1)),
+            [xy(15, 0), xy(14, 1), xy(15, 1), xy(15, 2)]
+        );
+        assert!(model.surrounding_mines().all(|(_, m)| (0..=7).contains(&m)));
+
+        // The solver follows the same neighborhood.
+        let solution = model.solver().solutions(1).pop().or_fail()?;
+        for p in solution {
+            model.handle_click(p);
+        }
+        assert!(matches!(model.state(), State::Won { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn wrapped_edges_have_full_neighborhoods() -> Result<()> {
+        let custom = |qs: &str| -> Result<Level> {
+            Level::from_qs(&format!("?width=16&height=20&mines=40&wormholes=0&{qs}"))
+                .or_fail()?
+                .level
+                .or_fail()
+        };
+        let model = start(custom("wrap=horizontal").or_fail()?, 0).or_fail()?;
+        let neighbors = |model: &Model, p| model.board.neighbors(p).collect::<Vec<_>>();
+        let xy = Position::from_xy;
+        assert_eq!(
+            neighbors(&model, xy(0, 0)),
+            [xy(15, 0), xy(0, 0), xy(1, 0), xy(15, 1), xy(0, 1), xy(1, 1)]
+        );
+        assert_eq!(model.wrap_position(xy(16, -1)), xy(0, -1));
+
+        let model = start(custom("wrap=both").or_fail()?, 0).or_fail()?;
+        assert_eq!(neighbors(&model, xy(15, 19)).len(), 9);
+        assert!(neighbors(&model, xy(15, 19)).contains(&xy(0, 0)));
+        assert_eq!(model.wrap_position(xy(16, -1)), xy(0, 19));
+        assert_eq!(model.solver().count_solutions(1), 1);
+
+        let model = start(custom("wrap=vertical&topology=hex").or_fail()?, 0).or_fail()?;
+        assert_eq!(
+            neighbors(&model, xy(0, 19)),
+            [
+                xy(0, 18),
+                xy(1, 18),
+                xy(0, 19),
+                xy(1, 19),
+                xy(0, 0),
+                xy(1, 0)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn neighborhood_rules() -> Result<()> {
+        for (name, neighborhood, count) in [
+            ("cross", Neighborhood::Cross, 5),
+            ("extended", Neighborhood::Extended, 25),
+            ("knight", Neighborhood::Knight, 9),
+        ] {
+            let qs = format!("?width=16&height=16&mines=60&wormholes=0&neighborhood={name}");
+            let level = Level::from_qs(&qs).or_fail()?.level.or_fail()?;
+            assert_eq!(level.rules().neighborhood, neighborhood);
+
+            let mut model = start(level, 0).or_fail()?;
+            let center = Position::from_xy(8, 8);
+            assert_eq!(model.board.neighbors(center).count(), count, "{name}");
+            assert!(model
+                .surrounding_mines()
+                .all(|(_, m)| (0..=count as isize).contains(&m)));
+
+            // Generation, the solver and the win check agree on the neighborhood.
+            for p in expected_mines(&model) {
+                model.handle_click(p);
+            }
+            assert!(matches!(model.state(), State::Won { .. }), "{name}");
+        }
+
+        let knight = Neighborhood::Knight.deltas();
+        assert!(knight.contains(&(1, 2)) && !knight.contains(&(1, 1)));
+        Ok(())
+    }
+
+    #[test]
+    fn follow_hints() -> Result<()> {
+        let mut model = start(Level::LargeWithWormhole, 3).or_fail()?;
+        let mines = expected_mines(&model);
+        let mut hints = 0;
+        loop {
+            model.show_hint();
+            let Some(hint) = model.hint().cloned() else {
+                break;
+            };
+            hints += 1;
+            assert_eq!(model.hints_used(), hints);
+
+            // A hint agrees with the solution and is justified by digits, one of which counts the cell.
+            assert_eq!(hint.mine, mines.contains(&hint.position));
+            assert_ne!(hint.mine, model.has_mine(hint.position));
+            let region = model.board_region();
+            assert!(hint
+                .reasons
+                .iter()
+                .all(|&p| region.contains(&p) && !model.has_wormhole(p)));
+            assert!(hint
+                .reasons
+                .iter()
+                .any(|&p| model.board.neighbors(p).any(|q| q == hint.position)));
+            model.handle_click(hint.position);
+        }
+        assert!(hints > 0);
+
+        // No hint is counted when none is found.
+        model.show_hint();
+        assert_eq!(model.hints_used(), hints);
+        Ok(())
+    }
+
+    #[test]
+    fn anti_mines_count_as_minus_one() -> Result<()> {
+        let level = Level::from_qs("?width=16&height=16&mines=40&wormholes=0&antimines=10")
+            .or_fail()?
+            .level
+            .or_fail()?;
+        let mut model = start(level, 0).or_fail()?;
+        let kind = |model: &Model, p: Position| model.board.cell(p).expected_kind;
+        let mines = expected_mines(&model);
+        let anti = mines
+            .iter()
+            .filter(|&&p| kind(&model, p) == MineKind::Anti)
+            .count();
+        assert_eq!(anti, 10);
+        assert!(model.surrounding_mines().any(|(_, m)| m < 0));
+        assert_eq!(model.difficulty(), Difficulty::default());
+        model.show_hint();
+        assert!(model.hint().is_none());
+
+        // A click cycles through a mine, an anti-mine and no mine.
+        let p = mines[0];
+        model.handle_click(p);
+        assert_eq!(model.mine_kind(p), Some(MineKind::Normal));
+        assert_eq!(model.remaining_mines(), 39);
+        model.handle_click(p);
+        assert_eq!(model.mine_kind(p), Some(MineKind::Anti));
+        assert_eq!(model.remaining_mines(), 39);
+        model.undo();
+        assert_eq!(model.mine_kind(p), Some(MineKind::Normal));
+        model.redo();
+        model.handle_click(p);
+        assert_eq!(model.mine_kind(p), None);
+        assert_eq!(model.remaining_mines(), 40);
+
+        for p in mines {
+            model.handle_click(p);
+            if kind(&model, p) == MineKind::Anti {
+                model.handle_click(p);
+            }
+        }
+        assert!(matches!(model.state(), State::Won { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn double_mines_count_twice() -> Result<()> {
+        let level = Level::from_qs("?width=16&height=16&mines=100&wormholes=0&doublemines=30")
+            .or_fail()?
+            .level
+            .or_fail()?;
+        let mut model = start(level, 0).or_fail()?;
+        let kind = |model: &Model, p: Position| model.board.cell(p).expected_kind;
+        let mines = expected_mines(&model);
+        assert_eq!(mines.len(), 70);
+        let doubles = mines
+            .iter()
+            .filter(|&&p| kind(&model, p) == MineKind::Double)
+            .count();
+        assert_eq!(doubles, 30);
+        assert_eq!(model.difficulty(), Difficulty::default());
+
+        // A click cycles through a mine, a double mine and no mine.
+        let p = mines[0];
+        model.handle_click(p);
+        assert_eq!(model.mine_kind(p), Some(MineKind::Normal));
+        assert_eq!(model.remaining_mines(), 99);
+        model.handle_click(p);
+        assert_eq!(model.mine_kind(p), Some(MineKind::Double));
+        assert_eq!(model.remaining_mines(), 98);
+        model.undo();
+        assert_eq!(model.mine_kind(p), Some(MineKind::Normal));
+        assert_eq!(model.remaining_mines(), 99);
+        model.redo();
+        model.handle_click(p);
+        assert_eq!(model.mine_kind(p), None);
+        assert_eq!(model.remaining_mines(), 100);
+
+        for &p in &mines {
+            model.handle_click(p);
+            if kind(&model, p) == MineKind::Double {
+                model.handle_click(p);
+            }
+        }
+        assert!(matches!(model.state(), State::Won { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn double_mines_are_skipped_without_enough_remaining_mines() -> Result<()> {
+        let level = Level::from_qs("?width=16&height=16&mines=2&wormholes=0&doublemines=1")
+            .or_fail()?
+            .level
+            .or_fail()?;
+        let mut model = start(level, 0).or_fail()?;
+        let (p0, p1) = (Position::from_xy(0, 0), Position::from_xy(1, 0));
+        model.handle_click(p0);
+        model.handle_click(p1);
+        assert_eq!(model.remaining_mines(), 0);
+        model.handle_click(p0);
+        assert_eq!(model.mine_kind(p0), None);
+        assert_eq!(model.remaining_mines(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn rating_large_clueless_board_is_bounded() -> Result<()> {
+        // The exhaustive rule gives up on this board instead of searching for minutes.
+        let level =
+            Level::from_qs("?width=16&height=16&mines=100&wormholes=150&wormholemode=clueless")
+                .or_fail()?
+                .level
+                .or_fail()?;
+        let model = start(level, 0).or_fail()?;
+        assert_eq!(model.difficulty().rating, 5);
+        assert!(model.difficulty().undecided_cells > 0);
+        Ok(())
+    }
+
+    #[test]
+    fn clueless_wormholes_hold_mines() -> Result<()> {
+        let level =
+            Level::from_qs("?width=16&height=16&mines=40&wormholes=100&wormholemode=clueless")
+                .or_fail()?
+                .level
+                .or_fail()?;
+        let mut model = start(level, 0).or_fail()?;
+        let mines = expected_mines(&model);
+        let hidden = mines
+            .iter()
+            .copied()
+            .find(|&p| model.has_wormhole(p))
+            .or_fail()?;
+        assert!(!model.is_hole(hidden));
+
+        // The solver considers wormhole cells but not their clues.
+        let solver = model.solver();
+        assert_eq!(solver.cells().len(), 256);
+        let solution = solver.solutions(1).pop().or_fail()?;
+        assert!(solution.iter().all(|p| mines.contains(p)));
+
+        let (last, others) = mines.split_last().or_fail()?;
+        for &p in others {
+            model.handle_click(p);
+        }
+        assert!(model.has_mine(hidden));
+        assert_eq!(model.state(), State::Playing);
+        model.handle_click(*last);
+        assert!(matches!(model.state(), State::Won { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn fog_reveals_clues_around_placed_mines() -> Result<()> {
+        let level = Level::from_qs("?width=16&height=16&mines=40&wormholes=0&fog=true")
+            .or_fail()?
+            .level
+            .or_fail()?;
+        let mut model = start(level, 0).or_fail()?;
+        let revealed = |model: &Model| {
+            model
+                .board
+                .region
+                .iter()
+                .filter(|&p| model.is_revealed(p))
+                .count()
+        };
+        assert!((9..=25).contains(&revealed(&model)));
+
+        let p = model
+            .board
+            .region
+            .iter()
+            .find(|&p| model.board.neighbors(p).all(|p| !model.is_revealed(p)))
+            .or_fail()?;
+        let before = revealed(&model);
+        model.handle_click(p);
+        assert!(model.board.neighbors(p).all(|p| model.is_revealed(p)));
+        assert!(revealed(&model) > before);
+
+        // Revealed clues stay visible after the mine is removed, even by undo.
+        model.handle_click(p);
+        assert!(!model.has_mine(p));
+        assert!(model.is_revealed(p));
+        model.undo();
+        model.undo();
+        assert!(!model.has_mine(p));
+        assert!(model.is_revealed(p));
+
+        model.show_hint();
+        assert!(model.hint().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn undo_and_redo() -> Result<()> {
+        let mut model = start(Level::Large, 0).or_fail()?;
+        let a = Position::from_xy(1, 2);
+        let b = Position::from_xy(3, 4);
+        model.handle_click(a);
+        model.handle_click(b);
+        model.handle_click(a);
+        assert_eq!(model.remaining_mines(), 98);
+
+        model.undo();
+        assert!(model.has_mine(a));
+        model.undo();
+        model.undo();
+        assert!(!model.has_mine(a) && !model.has_mine(b));
+        assert_eq!(model.remaining_mines(), 99);
+        model.undo();
+
+        model.redo();
+        model.redo();
+        assert!(model.has_mine(a) && model.has_mine(b));
+        assert_eq!(model.remaining_mines(), 97);
+
+        // A new toggle discards the redo history.
+        model.handle_click(b);
+        model.redo();
+        assert!(model.has_mine(a) && !model.has_mine(b));
+
+        // A mine is not placed again on a cell marked as safe in the meantime.
+        let c = Position::from_xy(5, 6);
+        model.handle_click(c);
+        model.undo();
+        model.handle_long_press(c);
+        model.redo();
+        assert!(!model.has_mine(c) && model.is_safe(c));
+        assert_eq!(model.remaining_mines(), 98);
+        model.handle_long_press(c);
+        model.redo();
+        assert!(model.has_mine(c));
+        assert_eq!(model.remaining_mines(), 97);
+        Ok(())
+    }
+
+    #[test]
+    fn undo_keeps_mine_off_safe_cell() -> Result<()> {
+        let mut model = start(Level::Large, 0).or_fail()?;
+        let a = Position::from_xy(1, 2);
+        model.handle_click(a);
+        model.handle_click(a);
+        model.handle_long_press(a);
+
+        // Undoing the removal would put the mine back on the safe cell.
+        model.undo();
+        assert!(!model.has_mine(a) && model.is_safe(a));
+        assert_eq!(model.remaining_mines(), 99);
+
+        model.handle_long_press(a);
+        model.undo();
+        assert!(model.has_mine(a));
+        assert_eq!(model.remaining_mines(), 98);
+        Ok(())
+    }
+
+    #[test]
+    fn undo_is_disabled_after_winning() -> Result<()> {
+        let mut model = start(Level::Small, 0).or_fail()?;
+        for p in expected_mines(&model) {
+            model.handle_click(p);
+        }
+        assert!(matches!(model.state(), State::Won { .. }));
+
+        model.undo();
+        assert_eq!(model.remaining_mines(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn date_from_unix_time() {
+        let date = |secs| Date::from_unix_time(Duration::from_secs(secs));
+        assert_eq!(date(0).to_string(), "1970-01-01");
+        assert_eq!(date(951_825_599).to_string(), "2000-02-29");
+        assert_eq!(date(1_700_000_000).to_string(), "2023-11-14");
+        assert_eq!(date(1_700_000_000).seed(), 20231114);
+    }
+
+    #[test]
+    fn parse_query_string() -> Result<()> {
+        let params = Level::from_qs("").or_fail()?;
+        assert!(params.level.is_none() && params.seed.is_none() && !params.unique);
+
+        let params = Level::from_qs("?seed=123&unique=true&gesture=true").or_fail()?;
+        assert!(params.level.is_none());
+        assert_eq!(params.seed, Some(123));
+        assert!(params.unique);
+
+        let params = Level::from_qs("?width=20&mines=30").or_fail()?;
+        assert_eq!(
+            params.level,
+            Some(Level::Custom {
+                width: 20,
+                height: 30,
+                mines: 30,
+                wormholes: 99,
+                rules: Rules::default(),
+            })
+        );
+        assert_eq!(params.start, None);
+
+        let params = Level::from_qs("?level=wormhole").or_fail()?;
+        assert_eq!(params.level, None);
+        assert_eq!(params.start, Some(Level::LargeWithWormhole));
+
+        let params = Level::from_qs("?level=custom&mines=10").or_fail()?;
+        assert!(params.level.is_some_and(|level| level.mines() == 10));
+        assert_eq!(params.start, params.level);
+
+        let params = Level::from_qs("?topology=hex").or_fail()?;
+        assert_eq!(params.level.or_fail()?.rules().topology, Topology::Hex);
+        assert!(Level::from_qs("?topology=tri").is_err());
+
+        let params = Level::from_qs("?wrap=vertical&ghost=true").or_fail()?;
+        let rules = params.level.or_fail()?.rules();
+        assert!(!rules.wrap_x && rules.wrap_y);
+        assert!(params.ghost_border);
+        assert!(Level::from_qs("?wrap=yes").is_err());
+        assert!(Level::from_qs("?neighborhood=queen").is_err());
+        assert!(Level::from_qs("?mines=10&antimines=11").is_err());
+        assert!(Level::from_qs("?antimines=10&neighborhood=extended").is_err());
+        assert!(Level::from_qs("?mines=10&doublemines=6").is_err());
+        assert!(Level::from_qs("?wormholemode=invisible").is_err());
+        assert!(Level::from_qs("?fog=yes").is_err());
+        assert!(Level::from_qs("?timeattack=0").is_err());
+        assert!(Level::from_qs("?timeattack=5&width=20").is_err());
+        let params = Level::from_qs("?marathon=true").or_fail()?;
+        assert_eq!(params.session, Some(SessionMode::Marathon));
+        assert!(Level::from_qs("?mines=10&antimines=4&doublemines=4").is_err());
+        assert!(
+            Level::from_qs("?width=16&height=16&mines=300&wormholes=0&doublemines=100").is_ok()
+        );
+        assert!(Level::from_qs("?neighborhood=cross&topology=hex").is_err());
+        assert!(Level::from_qs("?wrap=both&topology=hex&height=17").is_err());
+
+        assert!(Level::from_qs("?level=huge").is_err());
+        assert!(Level::from_qs("?level=small&width=20").is_err());
+        assert!(Level::from_qs("?width=15").is_err());
+        assert!(Level::from_qs("?seed=-1").is_err());
+        assert!(Level::from_qs("?unique=yes").is_err());
+        assert!(Level::from_qs("?width=16&height=16&mines=200&wormholes=57").is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,30 @@
+use crate::model::Level;
+use pagurus::spatial::Position;
+use std::time::Duration;
+
+/// Record of a game that is enough to reproduce it from the beginning.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Replay {
+    pub level: Level,
+    pub seed: u32,
+
+    /// Whether the board was generated in the unique-solution mode.
+    pub unique: bool,
+
+    pub events: Vec<ReplayEvent>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ReplayEvent {
+    /// Elapsed game time when the event happened.
+    pub time: Duration,
+
+    pub action: ReplayAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ReplayAction {
+    Click(Position),
+    Undo,
+    Redo,
+}
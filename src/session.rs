@@ -0,0 +1,151 @@
+use crate::model::Level;
+use std::time::Duration;
+
+/// Levels of the marathon mode, played in this order.
+pub const MARATHON_LEVELS: [Level; 3] = [Level::Small, Level::Large, Level::LargeWithWormhole];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum SessionMode {
+    /// Solve as many small boards as possible within the time limit.
+    TimeAttack { time_limit: Duration },
+
+    /// Solve [`MARATHON_LEVELS`] in order, where the times of the boards are summed up.
+    Marathon,
+}
+
+/// Series of boards played in a row.
+#[derive(Debug, Clone)]
+pub struct Session {
+    mode: SessionMode,
+
+    // Game clock time when the session started.
+    start_time: Duration,
+
+    solved: usize,
+    total_time: Duration,
+    finished: bool,
+}
+
+impl Session {
+    pub fn new(mode: SessionMode, start_time: Duration) -> Self {
+        Self {
+            mode,
+            start_time,
+            solved: 0,
+            total_time: Duration::ZERO,
+            finished: false,
+        }
+    }
+
+    /// Returns the level of the board to play next, or `None` if the marathon is complete.
+    pub fn level(&self) -> Option<Level> {
+        match self.mode {
+            SessionMode::TimeAttack { .. } => Some(Level::Small),
+            SessionMode::Marathon => MARATHON_LEVELS.get(self.solved).copied(),
+        }
+    }
+
+    /// Counts a solved board, which finishes the marathon after the last level.
+    pub fn record_win(&mut self, elapsed_time: Duration) {
+        if self.finished {
+            return;
+        }
+        self.solved += 1;
+        self.total_time += elapsed_time;
+        if self.level().is_none() {
+            self.finished = true;
+        }
+    }
+
+    /// Returns `true` if the time limit of the time-attack mode has passed at `now`.
+    pub fn is_time_up(&self, now: Duration) -> bool {
+        match self.mode {
+            SessionMode::TimeAttack { time_limit } => now - self.start_time >= time_limit,
+            SessionMode::Marathon => false,
+        }
+    }
+
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Returns the time shown on the header during the session.
+    ///
+    /// This is the remaining time in the time-attack mode, or the cumulative time (including
+    /// `board_time` of the board being played) in the marathon mode.
+    pub fn clock(&self, now: Duration, board_time: Duration) -> Duration {
+        match self.mode {
+            SessionMode::TimeAttack { time_limit } => {
+                time_limit.saturating_sub(now - self.start_time)
+            }
+            SessionMode::Marathon => self.total_time + board_time,
+        }
+    }
+
+    pub fn summary(&self) -> SessionSummary {
+        SessionSummary {
+            mode: self.mode,
+            solved: self.solved,
+            total_time: self.total_time,
+            finished: self.finished,
+        }
+    }
+}
+
+/// Result of a session (see `Game::query("sessionSummary")`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SessionSummary {
+    pub mode: SessionMode,
+    pub solved: usize,
+
+    /// Sum of the times of the solved boards.
+    pub total_time: Duration,
+
+    pub finished: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marathon_plays_each_level_once() {
+        let secs = Duration::from_secs;
+        let mut session = Session::new(SessionMode::Marathon, secs(100));
+        for (i, level) in MARATHON_LEVELS.into_iter().enumerate() {
+            assert_eq!(session.level(), Some(level));
+            assert!(!session.is_finished());
+            session.record_win(secs(10 * (i as u64 + 1)));
+        }
+        assert_eq!(session.level(), None);
+        assert!(session.is_finished());
+        assert!(!session.is_time_up(secs(10000)));
+        assert_eq!(session.clock(secs(1000), Duration::ZERO), secs(60));
+
+        let summary = session.summary();
+        assert_eq!((summary.solved, summary.total_time), (3, secs(60)));
+    }
+
+    #[test]
+    fn time_attack_ends_at_time_limit() {
+        let secs = Duration::from_secs;
+        let mode = SessionMode::TimeAttack {
+            time_limit: secs(60),
+        };
+        let mut session = Session::new(mode, secs(100));
+        session.record_win(secs(20));
+        session.record_win(secs(15));
+        assert_eq!(session.level(), Some(Level::Small));
+        assert_eq!(session.clock(secs(140), secs(5)), secs(20));
+        assert!(!session.is_time_up(secs(159)));
+        assert!(session.is_time_up(secs(160)));
+
+        session.finish();
+        session.record_win(secs(10));
+        assert_eq!(session.summary().solved, 2);
+    }
+}
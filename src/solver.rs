@@ -0,0 +1,672 @@
+use pagurus::spatial::Position;
+use std::collections::{HashMap, VecDeque};
+
+// Maximum number of search decisions spent on the exhaustive rule by a single [`Steps`] iterator.
+// Without it, rating a large board with few clues could take minutes.
+const MAX_EXHAUSTIVE_NODES: usize = 100_000;
+
+/// Constraint solver that deduces mine placements from the digits (clues) of a board.
+///
+/// A solver consists of cells that may hold a mine and clues that tell
+/// how many mines are placed in a set of cells.
+/// Wormholes are modelled by which of the two they are added as, depending on the wormhole mode:
+/// a hole (the default) is neither a cell nor a clue, so it never holds a mine,
+/// while a clueless wormhole is a cell without a clue, so it may hold a mine but hides its digit.
+#[derive(Debug, Default, Clone)]
+pub struct Solver {
+    cells: Vec<Position>,
+    index: HashMap<Position, usize>,
+    clues: Vec<Clue>,
+    mines: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Clue {
+    position: Position,
+    cells: Vec<usize>,
+    value: isize,
+}
+
+/// A single logical deduction made by [`Solver::steps()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub position: Position,
+    pub mine: bool,
+
+    /// Positions of the clues that justify this step.
+    pub reasons: Vec<Position>,
+
+    pub rule: Rule,
+}
+
+/// Deduction rules used by [`Solver::steps()`], from the simplest one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Rule {
+    /// All the unknown cells of a clue are mines, or none of them are.
+    SingleClue,
+
+    /// Comparing two overlapping clues decides the cells covered by only one of them.
+    CluePair,
+
+    /// Every possible placement agrees on the cell.
+    Exhaustive,
+}
+
+/// How hard it is to decide every cell by the deductions of [`Solver::steps()`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Difficulty {
+    pub single_clue_steps: usize,
+    pub clue_pair_steps: usize,
+    pub exhaustive_steps: usize,
+
+    /// Number of cells that cannot be decided without guessing
+    /// (including those left when the exhaustive rule runs out of its search budget).
+    pub undecided_cells: usize,
+
+    /// Weighted sum of the above (except for the single clue steps, which every board needs).
+    pub score: usize,
+
+    /// Coarse rating from 1 (easiest) to 5 (guesses are needed).
+    pub rating: u8,
+}
+
+impl Difficulty {
+    fn new(
+        single_clue_steps: usize,
+        clue_pair_steps: usize,
+        exhaustive_steps: usize,
+        undecided_cells: usize,
+    ) -> Self {
+        let score = clue_pair_steps + exhaustive_steps * 4 + undecided_cells * 10;
+        let rating = if undecided_cells > 0 {
+            5
+        } else if score >= 30 {
+            4
+        } else if score >= 10 {
+            3
+        } else if score > 0 {
+            2
+        } else {
+            1
+        };
+        Self {
+            single_clue_steps,
+            clue_pair_steps,
+            exhaustive_steps,
+            undecided_cells,
+            score,
+            rating,
+        }
+    }
+}
+
+impl Solver {
+    pub fn new(mines: usize) -> Self {
+        Self {
+            mines,
+            ..Self::default()
+        }
+    }
+
+    /// Makes a solver from a clue grid such as [`Model::surrounding_mines()`][crate::model::Model::surrounding_mines]
+    /// before any mines are placed, where `None` denotes a wormhole.
+    ///
+    /// Each clue covers the 3x3 square around its position.
+    pub fn from_clue_grid<I>(clues: I, mines: usize) -> Self
+    where
+        I: IntoIterator<Item = (Position, Option<isize>)>,
+    {
+        let clues = clues
+            .into_iter()
+            .filter_map(|(p, clue)| clue.map(|clue| (p, clue)))
+            .collect::<Vec<_>>();
+
+        let mut solver = Self::new(mines);
+        for &(p, _) in &clues {
+            solver.add_cell(p);
+        }
+        for &(p, clue) in &clues {
+            let neighbors = [-1, 0, 1]
+                .into_iter()
+                .flat_map(|y| [-1, 0, 1].map(|x| p.move_y(y).move_x(x)));
+            solver.add_clue(p, neighbors, clue);
+        }
+        solver
+    }
+
+    pub fn add_cell(&mut self, position: Position) {
+        if !self.index.contains_key(&position) {
+            self.index.insert(position, self.cells.len());
+            self.cells.push(position);
+        }
+    }
+
+    /// Adds a clue, shown at `position`, that the given cells contain `value` mines in total.
+    ///
+    /// Positions that have not been added by [`Solver::add_cell()`] are ignored.
+    pub fn add_clue<I>(&mut self, position: Position, cells: I, value: isize)
+    where
+        I: IntoIterator<Item = Position>,
+    {
+        let cells = cells
+            .into_iter()
+            .filter_map(|p| self.index.get(&p).copied())
+            .collect();
+        self.clues.push(Clue {
+            position,
+            cells,
+            value,
+        });
+    }
+
+    pub fn cells(&self) -> &[Position] {
+        &self.cells
+    }
+
+    /// Returns up to `limit` mine placements that satisfy all the clues.
+    pub fn solutions(&self, limit: usize) -> Vec<Vec<Position>> {
+        self.search()
+            .run(limit)
+            .into_iter()
+            .map(|solution| self.mine_positions(&solution))
+            .collect()
+    }
+
+    /// Counts the mine placements that satisfy all the clues, stopping at `limit`.
+    pub fn count_solutions(&self, limit: usize) -> usize {
+        self.search().run(limit).len()
+    }
+
+    /// Returns the cells whose state is the same in every solution
+    /// (`true` if the cell must hold a mine and `false` if it must be empty).
+    ///
+    /// If there are no solutions, an empty vector is returned.
+    pub fn deduce(&self) -> Vec<(Position, bool)> {
+        let known = vec![None; self.cells.len()];
+        let mut budget = usize::MAX;
+        self.forced(&known, false, &mut budget)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(i, mine)| (self.cells[i], mine))
+            .collect()
+    }
+
+    /// Returns an iterator over logical deductions, in the order a player could make them.
+    ///
+    /// Simple rules that look at one clue or a pair of overlapping clues are preferred.
+    /// Only when they are not enough, a cell is decided by checking every possible placement.
+    /// The iteration stops when no more cells can be decided without guessing,
+    /// or when checking the placements takes too long.
+    pub fn steps(&self) -> Steps<'_> {
+        let mut cell_clues = vec![Vec::new(); self.cells.len()];
+        for (i, clue) in self.clues.iter().enumerate() {
+            for &c in &clue.cells {
+                cell_clues[c].push(i);
+            }
+        }
+        Steps {
+            solver: self,
+            cell_clues,
+            known: vec![None; self.cells.len()],
+            pending: VecDeque::new(),
+            budget: MAX_EXHAUSTIVE_NODES,
+        }
+    }
+
+    /// Rates the board by counting the steps needed to solve it.
+    pub fn difficulty(&self) -> Difficulty {
+        let mut counts = [0; 3];
+        let mut decided = 0;
+        for step in self.steps() {
+            counts[step.rule as usize] += 1;
+            decided += 1;
+        }
+        Difficulty::new(counts[0], counts[1], counts[2], self.cells.len() - decided)
+    }
+
+    // Returns the unknown cells whose state is the same in every solution consistent with `known`,
+    // or `None` if the search takes more than `budget` decisions (the rest of which is left in it).
+    fn forced(
+        &self,
+        known: &[Option<bool>],
+        first_only: bool,
+        budget: &mut usize,
+    ) -> Option<Vec<(usize, bool)>> {
+        let mut search = self.search_with(known);
+        let Some(first) = search.run_within(1, budget)?.pop() else {
+            return Some(Vec::new());
+        };
+
+        // Cells that are decided by the propagation alone are forced without any search.
+        let mut propagated = self.search_with(known);
+        propagated.propagate_all();
+
+        let mut result = Vec::new();
+        let mut candidates = known.iter().map(|v| v.is_none()).collect::<Vec<_>>();
+        for i in 0..self.cells.len() {
+            if !candidates[i] {
+                continue;
+            }
+            if propagated.values[i].is_none() {
+                let mut search = self.search_with(known);
+                search.assign(i, !first[i]);
+                if let Some(other) = search.run_within(1, budget)?.pop() {
+                    for j in 0..self.cells.len() {
+                        if other[j] != first[j] {
+                            candidates[j] = false;
+                        }
+                    }
+                    continue;
+                }
+            }
+
+            result.push((i, first[i]));
+            if first_only {
+                break;
+            }
+        }
+        Some(result)
+    }
+
+    fn mine_positions(&self, solution: &[bool]) -> Vec<Position> {
+        (0..self.cells.len())
+            .filter(|&i| solution[i])
+            .map(|i| self.cells[i])
+            .collect()
+    }
+
+    fn search(&self) -> Search {
+        let mut search = Search::new(self.cells.len());
+        for clue in &self.clues {
+            search.add_constraint(clue.cells.clone(), clue.value);
+        }
+        search.add_constraint((0..self.cells.len()).collect(), self.mines as isize);
+        search
+    }
+
+    fn search_with(&self, known: &[Option<bool>]) -> Search {
+        let mut search = self.search();
+        for (i, value) in known.iter().enumerate() {
+            if let Some(mine) = *value {
+                search.assign(i, mine);
+            }
+        }
+        search
+    }
+}
+
+/// Iterator returned by [`Solver::steps()`].
+#[derive(Debug)]
+pub struct Steps<'a> {
+    solver: &'a Solver,
+    cell_clues: Vec<Vec<usize>>,
+    known: Vec<Option<bool>>,
+    pending: VecDeque<Step>,
+
+    // Remaining number of search decisions for the exhaustive rule.
+    budget: usize,
+}
+
+impl Steps<'_> {
+    fn deduce_next(&mut self) -> bool {
+        let deduced = self
+            .deduce_by_single_clue()
+            .map(|d| (Rule::SingleClue, d))
+            .or_else(|| self.deduce_by_clue_pair().map(|d| (Rule::CluePair, d)))
+            .or_else(|| self.deduce_exhaustively().map(|d| (Rule::Exhaustive, d)));
+        let Some((rule, deduced)) = deduced else {
+            return false;
+        };
+
+        for (i, mine, reasons) in deduced {
+            self.known[i] = Some(mine);
+            self.pending.push_back(Step {
+                position: self.solver.cells[i],
+                mine,
+                reasons: reasons
+                    .into_iter()
+                    .map(|c| self.solver.clues[c].position)
+                    .collect(),
+                rule,
+            });
+        }
+        true
+    }
+
+    // Returns the unknown cells of the clue and the number of mines still needed among them.
+    fn remaining(&self, clue: usize) -> (Vec<usize>, isize) {
+        let clue = &self.solver.clues[clue];
+        let mut unknowns = Vec::new();
+        let mut needed = clue.value;
+        for &c in &clue.cells {
+            match self.known[c] {
+                None => unknowns.push(c),
+                Some(true) => needed -= 1,
+                Some(false) => {}
+            }
+        }
+        (unknowns, needed)
+    }
+
+    fn deduce_by_single_clue(&self) -> Option<Vec<(usize, bool, Vec<usize>)>> {
+        (0..self.solver.clues.len()).find_map(|i| {
+            let (unknowns, needed) = self.remaining(i);
+            if unknowns.is_empty() || (needed != 0 && needed != unknowns.len() as isize) {
+                return None;
+            }
+            Some(
+                unknowns
+                    .into_iter()
+                    .map(|c| (c, needed != 0, vec![i]))
+                    .collect(),
+            )
+        })
+    }
+
+    // If clue `a` needs as many more mines than clue `b` as it has cells that `b` does not cover,
+    // all of those cells are mines and the cells only covered by `b` are empty.
+    fn deduce_by_clue_pair(&self) -> Option<Vec<(usize, bool, Vec<usize>)>> {
+        for a in 0..self.solver.clues.len() {
+            let (a_unknowns, a_needed) = self.remaining(a);
+            let mut others = a_unknowns
+                .iter()
+                .flat_map(|&c| self.cell_clues[c].iter().copied())
+                .filter(|&b| b != a)
+                .collect::<Vec<_>>();
+            others.sort_unstable();
+            others.dedup();
+
+            for b in others {
+                let (b_unknowns, b_needed) = self.remaining(b);
+                let a_only = a_unknowns
+                    .iter()
+                    .filter(|c| !b_unknowns.contains(c))
+                    .map(|&c| (c, true, vec![a, b]));
+                let b_only = b_unknowns
+                    .iter()
+                    .filter(|c| !a_unknowns.contains(c))
+                    .map(|&c| (c, false, vec![a, b]));
+                if a_needed - b_needed != a_only.clone().count() as isize {
+                    continue;
+                }
+
+                let deduced = a_only.chain(b_only).collect::<Vec<_>>();
+                if !deduced.is_empty() {
+                    return Some(deduced);
+                }
+            }
+        }
+        None
+    }
+
+    fn deduce_exhaustively(&mut self) -> Option<Vec<(usize, bool, Vec<usize>)>> {
+        let (i, mine) = self
+            .solver
+            .forced(&self.known, true, &mut self.budget)?
+            .pop()?;
+        Some(vec![(i, mine, self.cell_clues[i].clone())])
+    }
+}
+
+impl Iterator for Steps<'_> {
+    type Item = Step;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            if !self.deduce_next() {
+                return None;
+            }
+        }
+        self.pending.pop_front()
+    }
+}
+
+// Backtracking search over mine placements with constraint propagation.
+#[derive(Debug)]
+struct Search {
+    constraints: Vec<Constraint>,
+    var_constraints: Vec<Vec<usize>>,
+    values: Vec<Option<bool>>,
+    trail: Vec<usize>,
+    queue: Vec<usize>,
+
+    // Remaining number of decisions, and whether `run()` stopped because it reached zero.
+    budget: usize,
+    exhausted: bool,
+}
+
+#[derive(Debug)]
+struct Constraint {
+    vars: Vec<usize>,
+    value: isize,
+    mines: isize,
+    unknowns: usize,
+}
+
+impl Search {
+    fn new(vars: usize) -> Self {
+        Self {
+            constraints: Vec::new(),
+            var_constraints: vec![Vec::new(); vars],
+            values: vec![None; vars],
+            trail: Vec::new(),
+            queue: Vec::new(),
+            budget: usize::MAX,
+            exhausted: false,
+        }
+    }
+
+    fn add_constraint(&mut self, vars: Vec<usize>, value: isize) {
+        let i = self.constraints.len();
+        for &v in &vars {
+            self.var_constraints[v].push(i);
+        }
+        self.constraints.push(Constraint {
+            unknowns: vars.len(),
+            vars,
+            value,
+            mines: 0,
+        });
+    }
+
+    fn run(&mut self, limit: usize) -> Vec<Vec<bool>> {
+        let mut solutions = Vec::new();
+        if !self.propagate_all() {
+            return solutions;
+        }
+
+        // (trail length before the decision, decided variable, whether `true` has been tried)
+        let mut decisions: Vec<(usize, usize, bool)> = Vec::new();
+        loop {
+            let start = decisions.last().map_or(0, |d| d.1);
+            if let Some(v) = (start..self.values.len()).find(|&v| self.values[v].is_none()) {
+                if self.budget == 0 {
+                    self.exhausted = true;
+                    return solutions;
+                }
+                self.budget -= 1;
+                decisions.push((self.trail.len(), v, false));
+                if self.assign(v, false) && self.propagate() {
+                    continue;
+                }
+            } else {
+                solutions.push(self.values.iter().map(|v| *v == Some(true)).collect());
+                if solutions.len() >= limit {
+                    return solutions;
+                }
+            }
+
+            loop {
+                let Some((trail_len, v, tried)) = decisions.pop() else {
+                    return solutions;
+                };
+                self.undo(trail_len);
+                if !tried {
+                    decisions.push((trail_len, v, true));
+                    if self.assign(v, true) && self.propagate() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // Same as `run()`, but takes the decisions from `budget` and returns `None` if they run out.
+    fn run_within(&mut self, limit: usize, budget: &mut usize) -> Option<Vec<Vec<bool>>> {
+        self.budget = *budget;
+        let solutions = self.run(limit);
+        *budget = self.budget;
+        (!self.exhausted).then_some(solutions)
+    }
+
+    fn assign(&mut self, v: usize, mine: bool) -> bool {
+        self.values[v] = Some(mine);
+        self.trail.push(v);
+        let mut ok = true;
+        for &i in &self.var_constraints[v] {
+            let c = &mut self.constraints[i];
+            c.unknowns -= 1;
+            if mine {
+                c.mines += 1;
+            }
+            ok &= c.mines <= c.value && c.value <= c.mines + c.unknowns as isize;
+            self.queue.push(i);
+        }
+        ok
+    }
+
+    fn propagate_all(&mut self) -> bool {
+        self.queue = (0..self.constraints.len()).collect();
+        self.propagate()
+    }
+
+    fn propagate(&mut self) -> bool {
+        while let Some(i) = self.queue.pop() {
+            let c = &self.constraints[i];
+            let needed = c.value - c.mines;
+            if needed < 0 || needed > c.unknowns as isize {
+                self.queue.clear();
+                return false;
+            }
+            if c.unknowns == 0 || (needed != 0 && needed != c.unknowns as isize) {
+                continue;
+            }
+
+            let mine = needed != 0;
+            for j in 0..self.constraints[i].vars.len() {
+                let v = self.constraints[i].vars[j];
+                if self.values[v].is_none() && !self.assign(v, mine) {
+                    self.queue.clear();
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn undo(&mut self, trail_len: usize) {
+        self.queue.clear();
+        while self.trail.len() > trail_len {
+            let v = self.trail.pop().expect("unreachable");
+            let mine = self.values[v].take() == Some(true);
+            for &i in &self.var_constraints[v] {
+                let c = &mut self.constraints[i];
+                c.unknowns += 1;
+                if mine {
+                    c.mines -= 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Parses a board where `*` is a mine, `.` is an empty cell and `#` is a wormhole.
+    fn parse_board(board: &[&str]) -> (Solver, Vec<Position>) {
+        let cell = |p: Position| {
+            board
+                .get(p.y as usize)
+                .and_then(|row| row.as_bytes().get(p.x as usize))
+                .copied()
+        };
+        let mut clues = Vec::new();
+        let mut mines = Vec::new();
+        for (y, row) in board.iter().enumerate() {
+            for x in 0..row.len() {
+                let p = Position::from_xy(x as i32, y as i32);
+                if cell(p) == Some(b'*') {
+                    mines.push(p);
+                }
+                if cell(p) == Some(b'#') {
+                    clues.push((p, None));
+                    continue;
+                }
+                let clue = [-1, 0, 1]
+                    .into_iter()
+                    .flat_map(|dy| [-1, 0, 1].map(|dx| p.move_x(dx).move_y(dy)))
+                    .filter(|&p| cell(p) == Some(b'*'))
+                    .count();
+                clues.push((p, Some(clue as isize)));
+            }
+        }
+        (Solver::from_clue_grid(clues, mines.len()), mines)
+    }
+
+    #[test]
+    fn unique_board() {
+        let (solver, mines) = parse_board(&["*..", "...", "..*"]);
+        assert_eq!(solver.solutions(10), vec![mines.clone()]);
+        assert_eq!(solver.count_solutions(10), 1);
+
+        let forced = solver.deduce();
+        assert_eq!(forced.len(), 9);
+        assert!(forced.iter().all(|&(p, mine)| mine == mines.contains(&p)));
+    }
+
+    #[test]
+    fn ambiguous_board() {
+        // Both of the top-left corner cells see exactly the same clues.
+        let (solver, _) = parse_board(&["*.##", "..##", "####"]);
+        assert_eq!(solver.count_solutions(10), 4);
+        assert_eq!(solver.count_solutions(2), 2);
+        assert!(solver.deduce().is_empty());
+
+        let (solver, _) = parse_board(&["*#", "##"]);
+        assert_eq!(solver.count_solutions(10), 1);
+        assert_eq!(solver.deduce(), vec![(Position::from_xy(0, 0), true)]);
+    }
+
+    #[test]
+    fn steps_explain_deductions() {
+        let (solver, mines) = parse_board(&["*...", "....", "..*.", "....", "*..*"]);
+        let steps = solver.steps().collect::<Vec<_>>();
+        assert_eq!(steps.len(), solver.cells().len());
+        for step in &steps {
+            assert_eq!(step.mine, mines.contains(&step.position));
+            assert!(!step.reasons.is_empty());
+        }
+
+        // A `0` clue empties all of its cells at once.
+        let (solver, _) = parse_board(&["...*", "....", "....", "*..."]);
+        let first = solver.steps().next().unwrap();
+        assert!(!first.mine);
+        assert_eq!(first.reasons.len(), 1);
+    }
+
+    #[test]
+    fn rate_difficulty() {
+        let (solver, _) = parse_board(&["*..", "...", "..*"]);
+        let difficulty = solver.difficulty();
+        assert_eq!(difficulty.single_clue_steps, 9);
+        assert_eq!((difficulty.score, difficulty.rating), (0, 1));
+
+        // The two top-left cells can only be guessed.
+        let (solver, _) = parse_board(&["*.##", "..##", "####"]);
+        let difficulty = solver.difficulty();
+        assert_eq!(difficulty.undecided_cells, 4);
+        assert_eq!(difficulty.rating, 5);
+    }
+}
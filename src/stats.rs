@@ -0,0 +1,202 @@
+use crate::model::{Date, Level};
+use std::time::Duration;
+
+/// Play statistics of each level.
+///
+/// Custom levels are distinguished by their parameters.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Stats {
+    levels: Vec<LevelStats>,
+    #[serde(default)]
+    daily_record: Option<DailyRecord>,
+}
+
+impl Stats {
+    pub fn levels(&self) -> &[LevelStats] {
+        &self.levels
+    }
+
+    pub fn level(&self, level: Level) -> Option<&LevelStats> {
+        self.levels.iter().find(|s| s.level == level)
+    }
+
+    /// Returns the best time of the latest daily challenge won.
+    pub fn daily_record(&self) -> Option<DailyRecord> {
+        self.daily_record
+    }
+
+    fn level_mut(&mut self, level: Level) -> &mut LevelStats {
+        let i = match self.levels.iter().position(|s| s.level == level) {
+            Some(i) => i,
+            None => {
+                self.levels.push(LevelStats::new(level));
+                self.levels.len() - 1
+            }
+        };
+        &mut self.levels[i]
+    }
+
+    /// Records a new game, which breaks the winning streak if the previous game was not won.
+    pub fn record_start(&mut self, level: Level) {
+        let stats = self.level_mut(level);
+        if stats.playing {
+            stats.current_streak = 0;
+        }
+        stats.started += 1;
+        stats.playing = true;
+    }
+
+    pub fn record_win(&mut self, level: Level, elapsed_time: Duration) {
+        let stats = self.level_mut(level);
+        stats.won += 1;
+        stats.playing = false;
+        stats.total_time += elapsed_time;
+        stats.best_time = Some(
+            stats
+                .best_time
+                .map_or(elapsed_time, |t| t.min(elapsed_time)),
+        );
+        stats.current_streak += 1;
+        stats.best_streak = stats.best_streak.max(stats.current_streak);
+    }
+
+    /// Records a win of the daily challenge of `date`, which replaces the record of an older day.
+    pub fn record_daily_win(&mut self, date: Date, elapsed_time: Duration) {
+        match &mut self.daily_record {
+            Some(record) if record.date == date => {
+                record.best_time = record.best_time.min(elapsed_time);
+            }
+            Some(record) if record.date > date => {}
+            _ => {
+                self.daily_record = Some(DailyRecord {
+                    date,
+                    best_time: elapsed_time,
+                });
+            }
+        }
+    }
+}
+
+/// Best time of the daily challenge of a day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct DailyRecord {
+    pub date: Date,
+    pub best_time: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct LevelStats {
+    pub level: Level,
+    pub started: usize,
+    pub won: usize,
+    pub best_time: Option<Duration>,
+
+    /// Sum of the times of the won games.
+    pub total_time: Duration,
+
+    pub current_streak: usize,
+    pub best_streak: usize,
+
+    // Whether the last started game is still unfinished.
+    playing: bool,
+}
+
+impl LevelStats {
+    fn new(level: Level) -> Self {
+        Self {
+            level,
+            started: 0,
+            won: 0,
+            best_time: None,
+            total_time: Duration::ZERO,
+            current_streak: 0,
+            best_streak: 0,
+            playing: false,
+        }
+    }
+
+    pub fn average_time(&self) -> Option<Duration> {
+        (self.won > 0).then(|| self.total_time / self.won as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Rules;
+
+    #[test]
+    fn record_games() {
+        let custom = |mines| Level::Custom {
+            width: 16,
+            height: 16,
+            mines,
+            wormholes: 0,
+            rules: Default::default(),
+        };
+        let secs = Duration::from_secs;
+
+        let mut stats = Stats::default();
+        stats.record_start(Level::Small);
+        stats.record_win(Level::Small, secs(30));
+        stats.record_start(Level::Small);
+        stats.record_win(Level::Small, secs(10));
+        stats.record_start(custom(10));
+        stats.record_start(custom(20));
+        stats.record_win(custom(20), secs(100));
+
+        let small = stats.level(Level::Small).unwrap();
+        assert_eq!((small.started, small.won), (2, 2));
+        assert_eq!(small.best_time, Some(secs(10)));
+        assert_eq!(small.average_time(), Some(secs(20)));
+        assert_eq!((small.current_streak, small.best_streak), (2, 2));
+        assert_eq!(stats.level(custom(10)).unwrap().average_time(), None);
+        assert_eq!(stats.level(custom(20)).unwrap().won, 1);
+        assert!(stats.level(Level::Large).is_none());
+
+        // The fog-of-war mode has its own stats.
+        let fog = Level::Custom {
+            width: 16,
+            height: 16,
+            mines: 20,
+            wormholes: 0,
+            rules: Rules {
+                fog: true,
+                ..Rules::default()
+            },
+        };
+        assert!(stats.level(fog).is_none());
+
+        // An unfinished game breaks the streak.
+        stats.record_start(Level::Small);
+        stats.record_start(Level::Small);
+        stats.record_win(Level::Small, secs(20));
+        let small = stats.level(Level::Small).unwrap();
+        assert_eq!((small.started, small.won), (4, 3));
+        assert_eq!((small.current_streak, small.best_streak), (1, 2));
+    }
+
+    #[test]
+    fn record_daily_wins() {
+        let date = |day| Date {
+            year: 2023,
+            month: 11,
+            day,
+        };
+        let secs = Duration::from_secs;
+
+        let mut stats = Stats::default();
+        assert_eq!(stats.daily_record(), None);
+        stats.record_daily_win(date(14), secs(30));
+        stats.record_daily_win(date(14), secs(20));
+        stats.record_daily_win(date(14), secs(25));
+        assert_eq!(stats.daily_record().map(|r| r.best_time), Some(secs(20)));
+
+        // A record of an older day is ignored, while that of a newer day replaces it.
+        stats.record_daily_win(date(13), secs(10));
+        assert_eq!(stats.daily_record().map(|r| r.date), Some(date(14)));
+        stats.record_daily_win(date(15), secs(40));
+        let record = stats.daily_record().unwrap();
+        assert_eq!((record.date, record.best_time), (date(15), secs(40)));
+    }
+}
@@ -4,3 +4,7 @@ pub const RENDERING_TIMEOUT: TimeoutTag = TimeoutTag::new(0);
 pub const START_8X15_TIMEOUT: TimeoutTag = TimeoutTag::new(1);
 pub const START_16X30_TIMEOUT: TimeoutTag = TimeoutTag::new(2);
 pub const START_16X30_WITH_WORMHOLE_TIMEOUT: TimeoutTag = TimeoutTag::new(3);
+pub const LONG_PRESS_TIMEOUT: TimeoutTag = TimeoutTag::new(4);
+pub const START_DAILY_TIMEOUT: TimeoutTag = TimeoutTag::new(5);
+pub const SESSION_TIMEOUT: TimeoutTag = TimeoutTag::new(6);
+pub const NEXT_BOARD_TIMEOUT: TimeoutTag = TimeoutTag::new(7);
@@ -1,40 +1,145 @@
 use crate::{
-    asset::Assets,
-    model::{Model, State},
+    asset::{Assets, CellSprites},
+    model::{Date, MineKind, Model, Neighborhood, State, Topology},
+    session::SessionSummary,
 };
 use orfail::OrFail;
 use pagurus::{
-    event::{Event, MouseEvent},
+    event::{Event, Key, KeyEvent, MouseEvent},
     image::{Canvas, Color, Sprite},
     spatial::{Contains, Position, Region, Size},
     Result,
 };
+use std::time::Duration;
 
 const BACKGROUND_COLOR: Color = Color::rgb(133, 131, 131);
+const GHOST_CELL_COLOR: Color = Color::rgba(133, 131, 131, 160);
+const NEIGHBORHOOD_COLOR: Color = Color::rgb(64, 62, 62);
+const NEIGHBORHOOD_CENTER_COLOR: Color = Color::rgb(191, 59, 33);
+const GAUGE_FRAME_COLOR: Color = Color::rgb(64, 62, 62);
+const GAUGE_OFF_COLOR: Color = Color::rgb(102, 100, 100);
+const GAUGE_ON_COLORS: [Color; 5] = [
+    Color::rgb(32, 135, 39),
+    Color::rgb(130, 170, 40),
+    Color::rgb(220, 190, 40),
+    Color::rgb(230, 120, 30),
+    Color::rgb(191, 59, 33),
+];
 
 #[derive(Debug, Default)]
 pub struct Window {
     assets: Assets,
     help_button: Button,
+    hint_button: Button,
     start_8x15_button: Button,
     start_16x30_button: Button,
     start_16x30_with_wormhole_button: Button,
+    start_daily_button: Button,
     start_custom_button: Button,
+    replay_button: Button,
+    new_board_button: Button,
+    win_panel: Sprite,
+    session_panel: Sprite,
     pressing: bool,
+    pressed_cell: Option<Position>,
+    long_pressed: bool,
+    long_press_requested: bool,
+    pending_long_presses: usize,
+    cursor: Option<Position>,
+    ghost_border: bool,
+    session_clock: Option<Duration>,
+    session_summary: Option<SessionSummary>,
 }
 
 impl Window {
     const MARGIN_SIZE: u32 = 3;
     const CELL_SIZE: u32 = 16;
+    const WIN_PANEL_SIZE: Size = Size::from_wh(130, 94);
+    const SESSION_PANEL_SIZE: Size = Size::from_wh(72, 52);
 
     fn header_size(&self, model: &Model) -> Size {
         Size::from_wh(Self::CELL_SIZE * model.board_size().width, 24)
     }
 
     fn board_size(&self, model: &Model) -> Size {
+        let hex_margin = match model.level().rules().topology {
+            Topology::Square => 0,
+            Topology::Hex => Self::CELL_SIZE / 2,
+        };
+        let (ghost_x, ghost_y) = self.ghost_margin(model);
         Size::from_wh(
-            Self::CELL_SIZE * model.board_size().width,
-            Self::CELL_SIZE * model.board_size().height,
+            Self::CELL_SIZE * (model.board_size().width + ghost_x as u32 * 2) + hex_margin,
+            Self::CELL_SIZE * (model.board_size().height + ghost_y as u32 * 2),
+        )
+    }
+
+    /// Enables drawing copies of the cells on the opposite edges around wrapping boards.
+    pub fn set_ghost_border(&mut self, enabled: bool) {
+        self.ghost_border = enabled;
+    }
+
+    /// Sets the time shown on the header instead of the board time during a session.
+    pub fn set_session_clock(&mut self, clock: Option<Duration>) {
+        self.session_clock = clock;
+    }
+
+    /// Sets the summary of the finished session, which is shown over the board.
+    pub fn set_session_summary(&mut self, summary: Option<SessionSummary>) {
+        self.session_summary = summary;
+    }
+
+    // Returns the number of ghost columns and rows drawn beyond each edge.
+    fn ghost_margin(&self, model: &Model) -> (i32, i32) {
+        if !self.ghost_border {
+            return (0, 0);
+        }
+        let rules = model.level().rules();
+        (i32::from(rules.wrap_x), i32::from(rules.wrap_y))
+    }
+
+    // Returns the positions just outside the board where the cell at `p` appears again.
+    fn ghost_positions(&self, model: &Model, p: Position) -> Vec<Position> {
+        let region = model.board_region();
+        let (ghost_x, ghost_y) = self.ghost_margin(model);
+        let mut xs = vec![p.x];
+        if ghost_x > 0 {
+            if p.x == region.start().x {
+                xs.push(region.end().x);
+            }
+            if p.x == region.end().x - 1 {
+                xs.push(region.start().x - 1);
+            }
+        }
+        let mut ys = vec![p.y];
+        if ghost_y > 0 {
+            if p.y == region.start().y {
+                ys.push(region.end().y);
+            }
+            if p.y == region.end().y - 1 {
+                ys.push(region.start().y - 1);
+            }
+        }
+        ys.iter()
+            .flat_map(|&y| xs.iter().map(move |&x| Position::from_xy(x, y)))
+            .filter(|&ghost| ghost != p)
+            .collect()
+    }
+
+    // Odd rows of hexagonal boards are shifted right by half a cell.
+    fn row_shift(model: &Model, y: i32) -> i32 {
+        match model.level().rules().topology {
+            Topology::Hex if y.rem_euclid(2) == 1 => Self::CELL_SIZE as i32 / 2,
+            _ => 0,
+        }
+    }
+
+    // Returns the top-left pixel of a cell relative to the board.
+    fn cell_offset(&self, model: &Model, position: Position) -> Position {
+        let cell_size = Self::CELL_SIZE as i32;
+        let (ghost_x, ghost_y) = self.ghost_margin(model);
+        Position::from_xy(
+            (position.x + ghost_x) * cell_size + Self::row_shift(model, position.y),
+            (position.y + ghost_y) * cell_size,
         )
     }
 
@@ -52,16 +157,30 @@ impl Window {
             Region::new(self.header_region(model).position, Size::from_wh(20, 21)).move_y(1);
         let [start_8x16, start_16x30, start_16x30_with_wormhole, help] =
             self.assets.button_sprites().or_fail()?;
-        self.start_8x15_button = Button::new(button_region.move_x(145), start_8x16);
-        self.start_16x30_button = Button::new(button_region.move_x(169), start_16x30);
+        self.start_8x15_button = Button::new(button_region.move_x(116), start_8x16);
+        self.start_16x30_button = Button::new(button_region.move_x(139), start_16x30);
         self.start_16x30_with_wormhole_button =
-            Button::new(button_region.move_x(193), start_16x30_with_wormhole);
+            Button::new(button_region.move_x(162), start_16x30_with_wormhole);
+        let daily = self.assets.daily_button_sprite().or_fail()?;
+        self.start_daily_button = Button::new(button_region.move_x(185), daily);
         self.help_button = Button::new(button_region.move_x(232), help);
 
+        let hint = self.assets.hint_button_sprite().or_fail()?;
+        self.hint_button = Button::new(button_region.move_x(208), hint);
+
+        // The buttons on the win panel are placed relative to the panel.
+        self.win_panel = self.assets.win_panel_sprite().or_fail()?;
+        let button_region = Region::new(Position::from_xy(38, 66), Size::from_wh(20, 21));
+        let replay = self.assets.replay_button_sprite().or_fail()?;
+        self.replay_button = Button::new(button_region, replay);
+        let new_board = self.assets.new_board_button_sprite().or_fail()?;
+        self.new_board_button = Button::new(button_region.move_x(34), new_board);
+        self.session_panel = self.assets.session_panel_sprite().or_fail()?;
+
         let custom = self.assets.custom_button_sprite().or_fail()?;
         self.start_custom_button = Button::new(
             Region::new(
-                self.help_button.region.move_x(-32).position,
+                self.hint_button.region.move_x(-26).position,
                 Size::from_wh(23, 21),
             ),
             custom,
@@ -84,6 +203,17 @@ impl Window {
             .move_y(self.header_region(model).end().y + Self::MARGIN_SIZE as i32)
     }
 
+    // Returns the region of a panel of `size` at the center of the board.
+    fn panel_region(&self, model: &Model, size: Size) -> Region {
+        let board_region = self.board_region(model);
+        let position = board_region.position
+            + Position::from_xy(
+                (board_region.size.width - size.width) as i32 / 2,
+                (board_region.size.height - size.height) as i32 / 2,
+            );
+        Region::new(position, size)
+    }
+
     pub fn render(&self, canvas: &mut Canvas, model: &Model) -> Result<()> {
         canvas.fill_color(BACKGROUND_COLOR);
 
@@ -92,6 +222,7 @@ impl Window {
             .or_fail()?;
 
         self.help_button.render(canvas).or_fail()?;
+        self.hint_button.render(canvas).or_fail()?;
         if model.is_custom_mode() {
             self.start_custom_button.render(canvas).or_fail()?;
             let mut offset = self
@@ -105,18 +236,103 @@ impl Window {
             offset = offset.move_x(12);
             self.render_small_number(canvas, offset, model.board_size().height as usize)
                 .or_fail()?;
+
+            let neighborhood = model.level().rules().neighborhood;
+            if neighborhood != Neighborhood::Moore {
+                let offset = self
+                    .start_custom_button
+                    .render_region()
+                    .position
+                    .move_x(16)
+                    .move_y(4);
+                render_neighborhood_icon(canvas, offset, neighborhood);
+            }
         } else {
             self.start_8x15_button.render(canvas).or_fail()?;
             self.start_16x30_button.render(canvas).or_fail()?;
             self.start_16x30_with_wormhole_button
                 .render(canvas)
                 .or_fail()?;
+            self.start_daily_button.render(canvas).or_fail()?;
+            // A daily board resumed on a later day keeps the date it was started on.
+            let date = model.daily_date().unwrap_or(model.today());
+            self.render_daily_date(canvas, date).or_fail()?;
         }
 
         let board_region = self.board_region(model);
         self.render_board(&mut canvas.subregion(board_region), model)
             .or_fail()?;
 
+        if let Some(summary) = self.session_summary {
+            let panel_region = self.panel_region(model, Self::SESSION_PANEL_SIZE);
+            self.render_session_summary(&mut canvas.subregion(panel_region), summary)
+                .or_fail()?;
+        } else if let State::Won { elapsed_time } = model.state() {
+            let panel_region = self.panel_region(model, Self::WIN_PANEL_SIZE);
+            self.render_win_panel(&mut canvas.subregion(panel_region), model, elapsed_time)
+                .or_fail()?;
+        }
+
+        Ok(())
+    }
+
+    fn render_win_panel(
+        &self,
+        canvas: &mut Canvas,
+        model: &Model,
+        elapsed_time: Duration,
+    ) -> Result<()> {
+        canvas.draw_sprite(&self.win_panel);
+
+        let elapsed = std::cmp::min(999, elapsed_time.as_secs()) as usize;
+        self.render_number(canvas, Position::from_xy(50, 7), elapsed)
+            .or_fail()?;
+        let toggles = std::cmp::min(999, model.toggles());
+        self.render_number(canvas, Position::from_xy(113, 7), toggles)
+            .or_fail()?;
+
+        let level = model.level();
+        self.render_number(canvas, Position::from_xy(50, 31), level.mines())
+            .or_fail()?;
+        self.render_number(canvas, Position::from_xy(113, 31), level.wormholes())
+            .or_fail()?;
+        self.render_small_number(canvas, Position::from_xy(57, 56), level.width())
+            .or_fail()?;
+        self.render_small_number(canvas, Position::from_xy(69, 56), level.height())
+            .or_fail()?;
+
+        self.replay_button.render(canvas).or_fail()?;
+        self.new_board_button.render(canvas).or_fail()?;
+        Ok(())
+    }
+
+    fn render_session_summary(&self, canvas: &mut Canvas, summary: SessionSummary) -> Result<()> {
+        canvas.draw_sprite(&self.session_panel);
+
+        let total = std::cmp::min(999, summary.total_time.as_secs()) as usize;
+        self.render_number(canvas, Position::from_xy(50, 7), total)
+            .or_fail()?;
+        let solved = std::cmp::min(999, summary.solved);
+        self.render_number(canvas, Position::from_xy(50, 31), solved)
+            .or_fail()?;
+        Ok(())
+    }
+
+    // Draws the date of the daily challenge as "MM.DD" on the daily button.
+    fn render_daily_date(&self, canvas: &mut Canvas, date: Date) -> Result<()> {
+        let sprites = self.assets.small_digit_sprites().or_fail()?;
+        let offset = self.start_daily_button.render_region().position.move_y(14);
+        let digits = [
+            date.month / 10,
+            date.month % 10,
+            date.day / 10,
+            date.day % 10,
+        ];
+        for (x, digit) in [0, 4, 10, 14].into_iter().zip(digits) {
+            canvas
+                .offset(offset.move_x(x))
+                .draw_sprite(&sprites[digit as usize]);
+        }
         Ok(())
     }
 
@@ -142,6 +358,7 @@ impl Window {
             State::Playing => model.elapsed_time(),
             State::Won { elapsed_time } => elapsed_time,
         };
+        let elapsed_time = self.session_clock.unwrap_or(elapsed_time);
 
         let elapsed = std::cmp::min(999, elapsed_time.as_secs()) as usize;
         let offset = Position::from_xy(24 + 10 * 2, 5);
@@ -154,9 +371,30 @@ impl Window {
         self.render_number(canvas, offset, model.remaining_mines())
             .or_fail()?;
 
+        let x = if model.is_custom_mode() { 176 } else { 107 };
+        self.render_difficulty_gauge(canvas, Position::from_xy(x, 4), model.difficulty().rating);
+
         Ok(())
     }
 
+    // Draws a vertical gauge of five segments, filled from the bottom up to the rating.
+    fn render_difficulty_gauge(&self, canvas: &mut Canvas, offset: Position, rating: u8) {
+        let frame = Region::new(offset, Size::from_wh(6, 16));
+        canvas.subregion(frame).fill_color(GAUGE_FRAME_COLOR);
+        for (i, &color) in GAUGE_ON_COLORS.iter().enumerate() {
+            let segment = Region::new(
+                offset.move_x(1).move_y(13 - i as i32 * 3),
+                Size::from_wh(4, 2),
+            );
+            let color = if i < rating as usize {
+                color
+            } else {
+                GAUGE_OFF_COLOR
+            };
+            canvas.subregion(segment).fill_color(color);
+        }
+    }
+
     fn render_number(
         &self,
         canvas: &mut Canvas,
@@ -196,45 +434,117 @@ impl Window {
     }
 
     fn render_board(&self, canvas: &mut Canvas, model: &Model) -> Result<()> {
-        let cell_region = Size::square(Self::CELL_SIZE).to_region();
         let sprite = self.assets.cell_sprites().or_fail()?;
         for (position, mines) in model.surrounding_mines() {
-            if model.has_wormhole(position) {
+            if model.is_hole(position) {
                 continue;
             }
 
-            let cell_region = cell_region.shift_x(position.x).shift_y(position.y);
-            let mut canvas = canvas.offset(cell_region.position);
+            for ghost in self.ghost_positions(model, position) {
+                let mut canvas = canvas.offset(self.cell_offset(model, ghost));
+                self.render_cell(&mut canvas, &sprite, model, position, mines)
+                    .or_fail()?;
+                canvas
+                    .subregion(Size::square(Self::CELL_SIZE).to_region())
+                    .fill_color(GHOST_CELL_COLOR);
+            }
 
-            if mines <= 0 {
-                canvas.draw_sprite(&sprite.just);
-            } else {
-                canvas.draw_sprite(&sprite.over);
+            let mut canvas = canvas.offset(self.cell_offset(model, position));
+            self.render_cell(&mut canvas, &sprite, model, position, mines)
+                .or_fail()?;
+        }
+
+        if let Some(hint) = model.hint() {
+            for &position in &hint.reasons {
+                canvas
+                    .offset(self.cell_offset(model, position))
+                    .draw_sprite(&sprite.focus);
             }
 
-            if model.has_mine(position) {
-                canvas.draw_sprite(&sprite.mine);
+            let mut canvas = canvas.offset(self.cell_offset(model, hint.position));
+            if hint.mine {
+                canvas.draw_sprite(&sprite.mine_alpha);
+            }
+            canvas.draw_sprite(&sprite.focus);
+        }
 
-                let mut canvas = canvas.offset(Position::from_xy(8, 8));
-                #[allow(clippy::comparison_chain)]
-                if mines > 0 {
-                    canvas.draw_sprite(&sprite.mini_numbers[mines as usize - 1]);
-                } else if mines < 0 {
-                    canvas.draw_sprite(&sprite.mini_warning);
-                }
+        if let Some(cursor) = self.cursor.filter(|p| model.board_region().contains(p)) {
+            canvas
+                .offset(self.cell_offset(model, cursor))
+                .draw_sprite(&sprite.focus);
+        }
+
+        Ok(())
+    }
+
+    fn render_cell(
+        &self,
+        canvas: &mut Canvas,
+        sprite: &CellSprites,
+        model: &Model,
+        position: Position,
+        mines: isize,
+    ) -> Result<()> {
+        if model.has_wormhole(position) {
+            // Clueless wormholes (see `WormholeMode`) show only what the player placed.
+            canvas.draw_sprite(&sprite.clueless);
+            render_mark(canvas, sprite, model, position);
+            return Ok(());
+        }
+        if !model.is_revealed(position) {
+            canvas.draw_sprite(&sprite.hidden);
+            render_mark(canvas, sprite, model, position);
+            return Ok(());
+        }
+
+        // Negative clues are shown as numbers if anti-mines exist, or as warnings otherwise.
+        let negative = model.level().rules().has_anti_mines() && mines < 0;
+        if mines <= 0 && !negative {
+            canvas.draw_sprite(&sprite.just);
+        } else {
+            canvas.draw_sprite(&sprite.over);
+        }
+        if model.has_mine(position) || model.is_safe(position) {
+            render_mark(canvas, sprite, model, position);
+
+            #[allow(clippy::comparison_chain)]
+            if mines > 8 || negative {
+                render_clue_digits(canvas, sprite, Position::from_xy(8, 8), mines);
             } else if mines > 0 {
-                canvas.draw_sprite(&sprite.numbers[mines as usize - 1]);
+                canvas
+                    .offset(Position::from_xy(8, 8))
+                    .draw_sprite(&sprite.mini_numbers[mines as usize - 1]);
             } else if mines < 0 {
-                canvas.draw_sprite(&sprite.warning);
+                canvas
+                    .offset(Position::from_xy(8, 8))
+                    .draw_sprite(&sprite.mini_warning);
             }
+        } else if mines > 9 || negative {
+            render_clue_digits(canvas, sprite, Position::from_xy(8, 4), mines);
+        } else if mines > 0 {
+            canvas.draw_sprite(&sprite.numbers[mines as usize - 1]);
+        } else if mines < 0 {
+            canvas.draw_sprite(&sprite.warning);
         }
-
         Ok(())
     }
 
     pub fn handle_event(&mut self, event: Event, model: &mut Model) -> Result<()> {
-        if let Event::Mouse(event) = &event {
-            self.handle_mouse_event(event, model).or_fail()?;
+        if matches!(model.state(), State::Won { .. }) {
+            let mut event = event;
+            if let Event::Mouse(event) = &mut event {
+                event.set_position(
+                    event.position() - self.panel_region(model, Self::WIN_PANEL_SIZE).position,
+                );
+            }
+            self.replay_button.handle_event(&event).or_fail()?;
+            self.new_board_button.handle_event(&event).or_fail()?;
+        }
+
+        match &event {
+            Event::Mouse(event) => self.handle_mouse_event(event, model).or_fail()?,
+            Event::Key(event) => self.handle_key_event(event, model).or_fail()?,
+            _ => {}
         }
         if model.is_custom_mode() {
             self.start_custom_button.handle_event(&event).or_fail()?;
@@ -244,8 +554,10 @@ impl Window {
             self.start_16x30_with_wormhole_button
                 .handle_event(&event)
                 .or_fail()?;
+            self.start_daily_button.handle_event(&event).or_fail()?;
         }
         self.help_button.handle_event(&event).or_fail()?;
+        self.hint_button.handle_event(&event).or_fail()?;
 
         Ok(())
     }
@@ -254,6 +566,18 @@ impl Window {
         self.help_button.take_clicked()
     }
 
+    pub fn take_hint_button_clicked(&mut self) -> bool {
+        self.hint_button.take_clicked()
+    }
+
+    pub fn take_replay_button_clicked(&mut self) -> bool {
+        self.replay_button.take_clicked()
+    }
+
+    pub fn take_new_board_button_clicked(&mut self) -> bool {
+        self.new_board_button.take_clicked()
+    }
+
     pub fn take_start_8x15_button_clicked(&mut self) -> bool {
         self.start_8x15_button.take_clicked()
     }
@@ -266,24 +590,153 @@ impl Window {
         self.start_16x30_with_wormhole_button.take_clicked()
     }
 
+    pub fn take_start_daily_button_clicked(&mut self) -> bool {
+        self.start_daily_button.take_clicked()
+    }
+
     pub fn take_start_custom_button_clicked(&mut self) -> bool {
         self.start_custom_button.take_clicked()
     }
 
-    fn handle_mouse_event(&mut self, event: &MouseEvent, model: &mut Model) -> Result<()> {
-        let pixel_position = event.position();
+    fn handle_key_event(&mut self, event: &KeyEvent, model: &mut Model) -> Result<()> {
+        match event {
+            KeyEvent {
+                ctrl: true,
+                key: Key::Char('z'),
+                ..
+            } => model.undo(),
+            KeyEvent {
+                ctrl: true,
+                key: Key::Char('y'),
+                ..
+            } => model.redo(),
+            KeyEvent { ctrl: true, .. } | KeyEvent { alt: true, .. } => {}
+            KeyEvent {
+                key: Key::Left | Key::Char('h'),
+                ..
+            } => self.move_cursor(model, -1, 0),
+            KeyEvent {
+                key: Key::Right | Key::Char('l'),
+                ..
+            } => self.move_cursor(model, 1, 0),
+            KeyEvent {
+                key: Key::Up | Key::Char('k'),
+                ..
+            } => self.move_cursor(model, 0, -1),
+            KeyEvent {
+                key: Key::Down | Key::Char('j'),
+                ..
+            } => self.move_cursor(model, 0, 1),
+            KeyEvent {
+                key: Key::Char(' '),
+                ..
+            } => {
+                if let Some(cursor) = self.cursor {
+                    model.handle_click(cursor);
+                }
+            }
+            KeyEvent {
+                key: Key::Char(c), ..
+            } => {
+                // Keys act as the start buttons so that `Game` handles both in the same way.
+                let button = match c {
+                    '1' if !model.is_custom_mode() => &mut self.start_8x15_button,
+                    '2' if !model.is_custom_mode() => &mut self.start_16x30_button,
+                    '3' if !model.is_custom_mode() => &mut self.start_16x30_with_wormhole_button,
+                    '4' if !model.is_custom_mode() => &mut self.start_daily_button,
+                    'c' if model.is_custom_mode() => &mut self.start_custom_button,
+                    _ => return Ok(()),
+                };
+                button.state = ButtonState::Clicked;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
 
-        if matches!(event, MouseEvent::Down { .. }) {
-            self.pressing = true;
+    /// Moves the keyboard cursor, which appears at the top-left cell on the first move.
+    fn move_cursor(&mut self, model: &Model, dx: i32, dy: i32) {
+        let region = model.board_region();
+        if region.is_empty() {
+            return;
         }
+        let cursor = match self.cursor.filter(|p| region.contains(p)) {
+            None => region.start(),
+            Some(p) => Position::from_xy(
+                (p.x + dx).clamp(region.start().x, region.end().x - 1),
+                (p.y + dy).clamp(region.start().y, region.end().y - 1),
+            ),
+        };
+        self.cursor = Some(cursor);
+    }
 
-        if matches!(event, MouseEvent::Up { .. }) && self.pressing {
-            self.pressing = false;
+    /// Returns `true` if a long press timer (`tag::LONG_PRESS_TIMEOUT`) should be started.
+    pub fn take_long_press_requested(&mut self) -> bool {
+        std::mem::take(&mut self.long_press_requested)
+    }
+
+    pub fn handle_long_press_timeout(&mut self, model: &mut Model) {
+        // Timers cannot be cancelled, so only the one started by the latest press is effective.
+        self.pending_long_presses = self.pending_long_presses.saturating_sub(1);
+        if self.pending_long_presses > 0 || !self.pressing {
+            return;
+        }
+        if let Some(cell_position) = self.pressed_cell {
+            model.handle_long_press(cell_position);
+            self.long_pressed = true;
+        }
+    }
 
-            if self.board_region(model).contains(&pixel_position) {
-                let cell_pixel_position = pixel_position - self.board_region(model).start();
-                let cell_position = cell_pixel_position / Self::CELL_SIZE;
-                model.handle_click(cell_position);
+    fn cell_position(&self, model: &Model, pixel_position: Position) -> Option<Position> {
+        let board_region = self.board_region(model);
+        if !board_region.contains(&pixel_position) {
+            return None;
+        }
+        let cell_size = Self::CELL_SIZE as i32;
+        let (ghost_x, ghost_y) = self.ghost_margin(model);
+        let offset = pixel_position - board_region.start();
+        let y = offset.y / cell_size - ghost_y;
+        let x = (offset.x - Self::row_shift(model, y)).div_euclid(cell_size) - ghost_x;
+
+        // Ghost cells act as the cells they copy.
+        let position = model.wrap_position(Position::from_xy(x, y));
+        model
+            .board_size()
+            .to_region()
+            .contains(&position)
+            .then_some(position)
+    }
+
+    fn handle_mouse_event(&mut self, event: &MouseEvent, model: &mut Model) -> Result<()> {
+        let cell_position = self.cell_position(model, event.position());
+
+        match event {
+            MouseEvent::Down { .. } => {
+                self.cursor = None;
+                self.pressing = true;
+                self.long_pressed = false;
+                self.pressed_cell = cell_position;
+                if cell_position.is_some() {
+                    self.long_press_requested = true;
+                    self.pending_long_presses += 1;
+                }
+            }
+            MouseEvent::Move { .. } => {
+                if self.pressed_cell != cell_position {
+                    self.pressed_cell = None;
+                }
+            }
+            MouseEvent::Up { .. } => {
+                if !self.pressing {
+                    return Ok(());
+                }
+                self.pressing = false;
+                if self.long_pressed {
+                    return Ok(());
+                }
+                if let Some(cell_position) = cell_position {
+                    model.handle_click(cell_position);
+                }
             }
         }
 
@@ -291,6 +744,49 @@ impl Window {
     }
 }
 
+// Draws the cells counted by a clue as a 5x5 pixel pattern, with the clue cell in red.
+fn render_neighborhood_icon(canvas: &mut Canvas, offset: Position, neighborhood: Neighborhood) {
+    for &(x, y) in neighborhood.deltas() {
+        let color = if (x, y) == (0, 0) {
+            NEIGHBORHOOD_CENTER_COLOR
+        } else {
+            NEIGHBORHOOD_COLOR
+        };
+        canvas.draw_pixel(offset.move_x(x + 2).move_y(y + 2), color);
+    }
+}
+
+// Draws the mine placed on a cell, or the "no mine here" mark.
+fn render_mark(canvas: &mut Canvas, sprite: &CellSprites, model: &Model, position: Position) {
+    match model.mine_kind(position) {
+        Some(MineKind::Normal) => canvas.draw_sprite(&sprite.mine),
+        Some(MineKind::Anti) => canvas.draw_sprite(&sprite.anti_mine),
+        Some(MineKind::Double) => canvas.draw_sprite(&sprite.double_mine),
+        None if model.is_safe(position) => canvas.draw_sprite(&sprite.safe),
+        None => {}
+    }
+}
+
+// Draws a number that has no dedicated sprite (e.g., clues above 9) from its last digit at `offset`.
+fn render_clue_digits(
+    canvas: &mut Canvas,
+    sprite: &CellSprites,
+    mut offset: Position,
+    number: isize,
+) {
+    let mut first = true;
+    let mut n = number.unsigned_abs();
+    while n > 0 || first {
+        canvas.offset(offset).draw_sprite(&sprite.digits[n % 10]);
+        offset.x -= 8;
+        n /= 10;
+        first = false;
+    }
+    if number < 0 {
+        canvas.offset(offset).draw_sprite(&sprite.minus);
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct Button {
     region: Region,
@@ -373,3 +869,192 @@ pub enum ButtonState {
     Pressed,
     Clicked,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockSystem;
+    use crate::model::Level;
+
+    fn click(window: &mut Window, model: &mut Model, position: Position) -> Result<()> {
+        for event in [MouseEvent::Down { position }, MouseEvent::Up { position }] {
+            window.handle_event(Event::Mouse(event), model).or_fail()?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn daily_date_of_board_in_play() -> Result<()> {
+        let mut system = MockSystem::new();
+        let mut model = Model::default();
+        model.initialize(&mut system).or_fail()?;
+        model.start_game(&mut system, Level::Daily).or_fail()?;
+        let mut window = Window::default();
+        window.load_assets(&model).or_fail()?;
+        let before_midnight = crate::export::render_image(&window, &model, 1).or_fail()?;
+
+        system.advance(Duration::from_secs(24 * 60 * 60));
+        model.update_today(&system);
+        assert_ne!(model.daily_date(), Some(model.today()));
+        let after_midnight = crate::export::render_image(&window, &model, 1).or_fail()?;
+        assert!(before_midnight == after_midnight);
+        Ok(())
+    }
+
+    #[test]
+    fn click_cells() -> Result<()> {
+        let mut system = MockSystem::new();
+        let mut model = Model::default();
+        model.initialize(&mut system).or_fail()?;
+        model.start_game(&mut system, Level::Large).or_fail()?;
+        let mut window = Window::default();
+        window.load_assets(&model).or_fail()?;
+
+        // The board starts at (3, 30) and each cell is 16x16 pixels.
+        click(&mut window, &mut model, Position::from_xy(3, 30)).or_fail()?;
+        assert!(model.has_mine(Position::from_xy(0, 0)));
+
+        click(
+            &mut window,
+            &mut model,
+            Position::from_xy(3 + 16 * 5 + 15, 30 + 16 * 7),
+        )
+        .or_fail()?;
+        assert!(model.has_mine(Position::from_xy(5, 7)));
+
+        click(&mut window, &mut model, Position::from_xy(258, 509)).or_fail()?;
+        assert!(model.has_mine(Position::from_xy(15, 29)));
+        assert_eq!(model.remaining_mines(), 96);
+
+        // Outside of the board.
+        click(&mut window, &mut model, Position::from_xy(2, 30)).or_fail()?;
+        click(&mut window, &mut model, Position::from_xy(3, 29)).or_fail()?;
+        click(&mut window, &mut model, Position::from_xy(259, 510)).or_fail()?;
+        assert_eq!(model.remaining_mines(), 96);
+        Ok(())
+    }
+
+    #[test]
+    fn click_hex_cells() -> Result<()> {
+        let mut system = MockSystem::new();
+        let mut model = Model::default();
+        model.initialize(&mut system).or_fail()?;
+        let level = Level::from_qs("?wormholes=0&topology=hex")
+            .or_fail()?
+            .level
+            .or_fail()?;
+        model.set_custom_level(level);
+        model.start_game(&mut system, level).or_fail()?;
+        let mut window = Window::default();
+        window.load_assets(&model).or_fail()?;
+        assert_eq!(window.window_size(&model).width, 3 * 2 + 16 * 16 + 8);
+
+        // Even rows start at the left edge of the board.
+        click(&mut window, &mut model, Position::from_xy(3 + 15, 30)).or_fail()?;
+        assert!(model.has_mine(Position::from_xy(0, 0)));
+
+        // Odd rows are shifted right by half a cell.
+        click(&mut window, &mut model, Position::from_xy(3 + 7, 30 + 16)).or_fail()?;
+        click(&mut window, &mut model, Position::from_xy(3 + 8, 30 + 16)).or_fail()?;
+        assert!(model.has_mine(Position::from_xy(0, 1)));
+        click(
+            &mut window,
+            &mut model,
+            Position::from_xy(3 + 16 * 16 + 7, 30 + 16),
+        )
+        .or_fail()?;
+        assert!(model.has_mine(Position::from_xy(15, 1)));
+        assert_eq!(model.remaining_mines(), 96);
+        Ok(())
+    }
+
+    #[test]
+    fn click_ghost_cells() -> Result<()> {
+        let mut system = MockSystem::new();
+        let mut model = Model::default();
+        model.initialize(&mut system).or_fail()?;
+        let level = Level::from_qs("?wormholes=0&wrap=horizontal")
+            .or_fail()?
+            .level
+            .or_fail()?;
+        model.set_custom_level(level);
+        model.start_game(&mut system, level).or_fail()?;
+        let mut window = Window::default();
+        window.set_ghost_border(true);
+        window.load_assets(&model).or_fail()?;
+        assert_eq!(window.window_size(&model).width, 3 * 2 + 16 * 18);
+
+        // The ghost column on the left copies the rightmost column and vice versa.
+        click(&mut window, &mut model, Position::from_xy(3, 30)).or_fail()?;
+        assert!(model.has_mine(Position::from_xy(15, 0)));
+        click(&mut window, &mut model, Position::from_xy(3 + 16, 30 + 16)).or_fail()?;
+        assert!(model.has_mine(Position::from_xy(0, 1)));
+        click(
+            &mut window,
+            &mut model,
+            Position::from_xy(3 + 16 * 17, 30 + 32),
+        )
+        .or_fail()?;
+        assert!(model.has_mine(Position::from_xy(0, 2)));
+        assert_eq!(model.remaining_mines(), 96);
+        Ok(())
+    }
+
+    #[test]
+    fn keyboard_play() -> Result<()> {
+        let mut system = MockSystem::new();
+        let mut model = Model::default();
+        model.initialize(&mut system).or_fail()?;
+        let mut window = Window::default();
+        window.load_assets(&model).or_fail()?;
+
+        let press = |window: &mut Window, model: &mut Model, key: Key| {
+            window.handle_event(Event::Key(key.into()), model)
+        };
+
+        press(&mut window, &mut model, Key::Char('2')).or_fail()?;
+        assert!(window.take_start_16x30_button_clicked());
+        model.start_game(&mut system, Level::Small).or_fail()?;
+
+        // The cursor starts at the top-left cell of the 8x15 region and stays inside it.
+        let start = model.board_region().start();
+        for key in [
+            Key::Down,
+            Key::Char('l'),
+            Key::Char('l'),
+            Key::Char('h'),
+            Key::Up,
+            Key::Up,
+        ] {
+            press(&mut window, &mut model, key).or_fail()?;
+        }
+        assert_eq!(window.cursor, Some(start.move_x(1)));
+
+        press(&mut window, &mut model, Key::Char(' ')).or_fail()?;
+        assert!(model.has_mine(start.move_x(1)));
+        press(&mut window, &mut model, Key::Char(' ')).or_fail()?;
+        assert!(!model.has_mine(start.move_x(1)));
+
+        // Mouse operations hide the cursor.
+        click(&mut window, &mut model, Position::from_xy(0, 0)).or_fail()?;
+        assert_eq!(window.cursor, None);
+        Ok(())
+    }
+
+    #[test]
+    fn click_buttons() -> Result<()> {
+        let mut model = Model::default();
+        model.initialize(&mut MockSystem::new()).or_fail()?;
+        let mut window = Window::default();
+        window.load_assets(&model).or_fail()?;
+
+        click(&mut window, &mut model, Position::from_xy(134, 14)).or_fail()?;
+        assert!(window.take_start_8x15_button_clicked());
+        assert!(!window.take_start_8x15_button_clicked());
+
+        click(&mut window, &mut model, Position::from_xy(245, 14)).or_fail()?;
+        assert!(window.take_help_button_clicked());
+        assert!(!window.take_hint_button_clicked());
+        Ok(())
+    }
+}